@@ -1,10 +1,14 @@
+use aeonium_menu::protocol::{Command, Item, PROTOCOL_VERSION, Reply, read_message, write_message};
+use aeonium_menu::render::{self, DEFAULT_ICON};
+use aeonium_menu::theme::Theme;
 use anyhow::{Context, bail};
-use raylib::ffi::{Image, LoadImageFromMemory, LoadTextureFromImage, UnloadImage};
+use raylib::ffi::{Image, LoadFontEx, LoadImageFromMemory, LoadTextureFromImage, UnloadImage};
 use raylib::prelude::*;
 use tracing::{debug, error, info, trace, warn};
 
 use std::ffi::CString;
-use std::io::BufRead;
+use std::io::{BufReader, Stdin};
+use std::path::PathBuf;
 use std::sync::mpsc;
 use std::{env, io, thread};
 
@@ -13,22 +17,50 @@ mod ring_menu;
 const WIN_W: i32 = 1920;
 const WIN_H: i32 = 1080;
 
-static DEFAULT_ICON_DATA: &[u8] = include_bytes!("../../../assets/default.png");
+const USAGE: &str = "\
+Usage: aeonium-gui
+       aeonium-gui --render-to <png> [--highlight <n>] [--size <width>x<height>]
+
+Started by aeonium-menu, which sends it messages on stdin.
+
+Options:
+  --render-to <png>     Draw the ring into an image instead of a window and exit. The theme
+                        and entries are read from the messages on stdin, no handshake needed
+  --highlight <n>       Highlight entry n, counting from 0
+  --size <w>x<h>        Size of the image [default: 1920x1080]";
+
+/// Drawing into an image, asked for with `--render-to`
+struct RenderArgs {
+    path: PathBuf,
+    highlight: Option<usize>,
+    width: u32,
+    height: u32,
+}
+
+/// One level of the ring, nested rings are pushed on top of the top level one
+struct Ring {
+    items: Vec<Item>,
+    icon_textures: Vec<Texture2D>,
+}
 
 fn main() -> anyhow::Result<()> {
+    // Stdout carries the protocol replies
     tracing_subscriber::fmt()
         .with_max_level(tracing::Level::WARN)
+        .with_writer(io::stderr)
         .init();
 
-    let mut args = env::args_os().skip(1);
-    let segments: usize = args
-        .next()
-        .context("Expected argument for number of segments")?
-        .to_string_lossy()
-        .parse()
-        .context("Failed to parse segments argument as usize")?;
+    let mut stdin = BufReader::new(io::stdin());
+
+    if let Some(args) = parse_args()? {
+        return render_to_file(&mut stdin, &args);
+    }
+
+    handshake(&mut stdin)?;
 
     let mut highlight_idx: Option<usize> = None;
+    let mut theme = Theme::default();
+    let mut rings: Vec<Ring> = Vec::new();
 
     set_trace_log_callback(|lvl, msg| match lvl {
         TraceLogLevel::LOG_ALL | TraceLogLevel::LOG_TRACE => trace!("{msg}"),
@@ -49,85 +81,270 @@ fn main() -> anyhow::Result<()> {
 
     rl.set_target_fps(30);
 
-    let icon_paths: Vec<String> = args.map(|s| s.to_string_lossy().into_owned()).collect();
+    let mut font = load_font(&rl, &theme);
 
-    let mut icon_textures = Vec::new();
+    let rx = input_checker_thread(stdin);
 
-    for path in icon_paths {
-        let texture = if path == "default" {
-            load_default_icon(DEFAULT_ICON_DATA)?
-        } else {
-            match rl.load_texture(&thread, &path) {
-                Ok(texture) => texture,
-                Err(err) => {
-                    warn!("Failed to load icon `{path}`: {err}");
-                    warn!("Falling back to default icon");
-                    load_default_icon(DEFAULT_ICON_DATA)
-                        .context("Failed to load default icon")?
+    'render_loop: while !rl.window_should_close() {
+        while let Ok(command) = rx.try_recv() {
+            match command {
+                Command::Highlight { index } => {
+                    highlight_idx = Some(index);
+                }
+                Command::Theme { theme: new_theme } => {
+                    theme = new_theme;
+                    font = load_font(&rl, &theme);
+                }
+                Command::Items { items } => {
+                    rings.clear();
+                    rings.push(load_ring(&mut rl, &thread, items)?);
+                    highlight_idx = None;
+                }
+                Command::PushMenu { items } => {
+                    rings.push(load_ring(&mut rl, &thread, items)?);
+                    highlight_idx = None;
+                }
+                Command::PopMenu => {
+                    if rings.len() > 1 {
+                        rings.pop();
+                    }
+                    highlight_idx = None;
+                }
+                Command::Hello { .. } => warn!("Unexpected second handshake"),
+                Command::Close => {
+                    break 'render_loop;
                 }
             }
+        }
+
+        let mut d = rl.begin_drawing(&thread);
+        d.clear_background(Color::new(0, 0, 0, 0));
+
+        if let Some(ring) = rings.last() {
+            ring_menu::draw(
+                &mut d,
+                WIN_H as f32,
+                WIN_W as f32,
+                highlight_idx,
+                &ring.items,
+                &ring.icon_textures,
+                &font,
+                &theme,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_args() -> anyhow::Result<Option<RenderArgs>> {
+    let mut render_to: Option<PathBuf> = None;
+    let mut highlight = None;
+    let mut size = None;
+
+    let mut argv = env::args_os().skip(1);
+    while let Some(arg) = argv.next() {
+        let mut value = || {
+            argv.next()
+                .with_context(|| format!("`{}` expects a value", arg.display()))
         };
 
-        icon_textures.push(texture);
+        match arg.to_str() {
+            Some("-h" | "--help") => {
+                println!("{USAGE}");
+                std::process::exit(0);
+            }
+            Some("--render-to") => {
+                render_to = Some(value()?.into());
+            }
+            Some("--highlight") => {
+                let idx = value()?;
+                highlight = Some(
+                    idx.to_string_lossy()
+                        .parse()
+                        .with_context(|| format!("Invalid entry index `{}`", idx.display()))?,
+                );
+            }
+            Some("--size") => {
+                let value = value()?.to_string_lossy().into_owned();
+                let parsed = value
+                    .split_once('x')
+                    .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)));
+                size = Some(parsed.with_context(|| format!("Invalid size `{value}`"))?);
+            }
+            _ => bail!("Unknown argument `{}`\n\n{USAGE}", arg.display()),
+        }
     }
 
-    let rx = input_checker_thread();
+    let Some(path) = render_to else {
+        if highlight.is_some() || size.is_some() {
+            bail!("`--highlight` and `--size` only apply to `--render-to`");
+        }
+        return Ok(None);
+    };
 
-    'render_loop: while !rl.window_should_close() {
-        while let Ok(msg) = rx.try_recv() {
-            match msg {
-                Some(idx) => {
-                    highlight_idx = Some(idx);
-                }
-                None => {
-                    break 'render_loop;
+    let (width, height) = size.unwrap_or((WIN_W as u32, WIN_H as u32));
+    Ok(Some(RenderArgs {
+        path,
+        highlight,
+        width,
+        height,
+    }))
+}
+
+/// Replays the messages on stdin without a window and saves the ring they end with
+fn render_to_file(stdin: &mut BufReader<Stdin>, args: &RenderArgs) -> anyhow::Result<()> {
+    let mut theme = Theme::default();
+    let mut rings: Vec<Vec<Item>> = Vec::new();
+    let mut highlight_idx = None;
+
+    while let Some(command) = read_message::<Command>(stdin)? {
+        match command {
+            Command::Hello { .. } => {}
+            Command::Theme { theme: new_theme } => theme = new_theme,
+            Command::Items { items } => {
+                rings = vec![items];
+                highlight_idx = None;
+            }
+            Command::PushMenu { items } => {
+                rings.push(items);
+                highlight_idx = None;
+            }
+            Command::PopMenu => {
+                if rings.len() > 1 {
+                    rings.pop();
                 }
+                highlight_idx = None;
             }
+            Command::Highlight { index } => highlight_idx = Some(index),
+            Command::Close => break,
         }
+    }
 
-        let mut d = rl.begin_drawing(&thread);
-        d.clear_background(Color::new(0, 0, 0, 0));
+    let items = rings.last().map(Vec::as_slice).unwrap_or_default();
+    let pixmap = render::render(
+        args.width,
+        args.height,
+        items,
+        args.highlight.or(highlight_idx),
+        &theme,
+    )?;
+
+    pixmap
+        .save_png(&args.path)
+        .with_context(|| format!("Failed to write {}", args.path.display()))
+}
 
-        ring_menu::draw(
-            &mut d,
-            WIN_H as f32,
-            WIN_W as f32,
-            highlight_idx,
-            segments,
-            &icon_textures,
+/// Waits for `Hello` from the menu and answers with `Ready` if its protocol version matches
+fn handshake(stdin: &mut BufReader<Stdin>) -> anyhow::Result<()> {
+    let mut stdout = io::stdout();
+
+    let version = match read_message::<Command>(stdin)? {
+        Some(Command::Hello { version }) => version,
+        Some(other) => bail!("Expected a handshake, got {other:?}"),
+        None => bail!("Stdin closed before the handshake"),
+    };
+
+    if version != PROTOCOL_VERSION {
+        let message = format!(
+            "Protocol version {version} isn't supported, this GUI speaks version {PROTOCOL_VERSION}"
+        );
+        write_message(
+            &mut stdout,
+            &Reply::Error {
+                message: message.clone(),
+            },
         )?;
+        bail!(message);
     }
 
-    Ok(())
+    write_message(
+        &mut stdout,
+        &Reply::Ready {
+            version: PROTOCOL_VERSION,
+        },
+    )
 }
 
-fn input_checker_thread() -> mpsc::Receiver<Option<usize>> {
-    let (tx, rx) = mpsc::channel();
-    let stdin = io::stdin();
+/// Font from the theme, raylib's built in one when unset or unloadable
+fn load_font(rl: &RaylibHandle, theme: &Theme) -> WeakFont {
+    let Some(path) = &theme.font else {
+        return rl.get_font_default();
+    };
 
-    thread::spawn(move || {
-        for line in stdin.lock().lines() {
-            let text = match line {
-                Ok(t) => t,
+    let Ok(c_path) = CString::new(path.to_string_lossy().as_bytes()) else {
+        warn!("Invalid font path {}", path.display());
+        return rl.get_font_default();
+    };
+
+    // Latin, Greek and Cyrillic, raylib only loads ASCII by default
+    let mut codepoints: Vec<i32> = (0x20..=0x24F).chain(0x370..=0x4FF).collect();
+    // Rasterised once at the bigger size, scaling down looks better than scaling up
+    let size = theme.font_size.max(theme.label_font_size) as i32;
+
+    let font = unsafe {
+        LoadFontEx(
+            c_path.as_ptr(),
+            size,
+            codepoints.as_mut_ptr(),
+            codepoints.len() as i32,
+        )
+    };
+
+    if font.glyphs.is_null() || font.texture.id == 0 {
+        warn!("Failed to load font {}", path.display());
+        return rl.get_font_default();
+    }
+
+    // Lives as long as the window, so it's never unloaded
+    unsafe { WeakFont::from_raw(font) }
+}
+
+fn load_ring(
+    rl: &mut RaylibHandle,
+    thread: &RaylibThread,
+    items: Vec<Item>,
+) -> anyhow::Result<Ring> {
+    let mut icon_textures = Vec::new();
+
+    for item in &items {
+        let texture = match &item.icon {
+            None => load_default_icon(DEFAULT_ICON)?,
+            Some(path) => match rl.load_texture(thread, &path.to_string_lossy()) {
+                Ok(texture) => texture,
                 Err(err) => {
-                    warn!("Error reading stdin: {err}");
-                    continue;
+                    warn!("Failed to load icon `{}`: {err}", path.display());
+                    warn!("Falling back to default icon");
+                    load_default_icon(DEFAULT_ICON)
+                        .context("Failed to load default icon")?
                 }
-            };
+            },
+        };
 
-            let trimmed = text.trim();
+        icon_textures.push(texture);
+    }
 
-            if let Some(idx_str) = trimmed.to_uppercase().strip_prefix("HIGHLIGHT ") {
-                match idx_str.trim().parse::<usize>() {
-                    Ok(idx) => {
-                        let _ = tx.send(Some(idx));
-                    }
-                    Err(_) => warn!("Invalid index in `{trimmed}`"),
+    Ok(Ring {
+        items,
+        icon_textures,
+    })
+}
+
+fn input_checker_thread(mut stdin: BufReader<Stdin>) -> mpsc::Receiver<Command> {
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        loop {
+            match read_message::<Command>(&mut stdin) {
+                Ok(Some(command)) => {
+                    let _ = tx.send(command);
+                }
+                // The menu went away, nobody is left to close the ring
+                Ok(None) => {
+                    let _ = tx.send(Command::Close);
+                    break;
                 }
-            } else if trimmed.eq_ignore_ascii_case("QUIT") {
-                let _ = tx.send(None);
-            } else {
-                warn!("Unexpected input `{trimmed}`");
+                Err(err) => warn!("{err:#}"),
             }
         }
     });
@@ -1,22 +1,38 @@
+use aeonium_menu::geometry::{self, RingGeometry, Segment};
+use aeonium_menu::protocol::Item;
+use aeonium_menu::theme::{Rgba, Theme};
 use anyhow::ensure;
 use raylib::core::color::Color;
 use raylib::core::drawing::RaylibDraw;
 use raylib::drawing::RaylibDrawHandle;
 use raylib::math::{Rectangle, Vector2};
 use raylib::prelude::RaylibTexture2D;
+use raylib::text::{RaylibFont, WeakFont};
 use raylib::texture::Texture2D;
 
-const COLOR_TRANSLUCENT_BLUE: Color = Color::new(100, 149, 237, 77);
-const COLOR_DARK_BLUE: Color = Color::new(31, 102, 229, 220);
+fn color(rgba: Rgba) -> Color {
+    Color::new(rgba.r, rgba.g, rgba.b, rgba.a)
+}
+
+fn vector(point: geometry::Point) -> Vector2 {
+    Vector2::new(point.x, point.y)
+}
+
+fn rectangle(rect: geometry::Rect) -> Rectangle {
+    Rectangle::new(rect.x, rect.y, rect.width, rect.height)
+}
 
 pub fn draw(
     d: &mut RaylibDrawHandle,
     screen_h: f32,
     screen_w: f32,
     highlight: Option<usize>,
-    segments: usize,
+    items: &[Item],
     icon_textures: &[Texture2D],
+    font: &WeakFont,
+    theme: &Theme,
 ) -> anyhow::Result<()> {
+    let segments = items.len();
     ensure!(
         icon_textures.len() == segments,
         "icon_textures length mismatch: {} icons != {} segments",
@@ -24,20 +40,18 @@ pub fn draw(
         segments
     );
 
-    let center = Vector2::new(screen_w / 2.0, screen_h / 2.0);
-    let outer_radius = screen_h.min(screen_w) * 0.25;
-    let inner_radius = outer_radius * 0.75;
-
-    let gap_angle = 2.0;
-    let total_gap = gap_angle * segments as f32;
-    let angle_per_segment = (360.0 - total_gap) / segments as f32;
-
-    let mut start_angle = -90.0;
+    d.draw_rectangle(
+        0,
+        0,
+        screen_w as i32,
+        screen_h as i32,
+        color(theme.background),
+    );
 
-    for (idx, icon_tex) in icon_textures.iter().enumerate() {
-        let end_angle = start_angle + angle_per_segment;
+    let geometry = RingGeometry::new(screen_w, screen_h, segments, theme);
 
-        let color = match highlight {
+    for (idx, (item, icon_tex)) in items.iter().zip(icon_textures).enumerate() {
+        let highlighted = match highlight {
             Some(h_idx) => {
                 ensure!(
                     h_idx < segments,
@@ -45,84 +59,173 @@ pub fn draw(
                     h_idx,
                     segments
                 );
-
-                if h_idx == idx {
-                    COLOR_DARK_BLUE
-                } else {
-                    COLOR_TRANSLUCENT_BLUE
-                }
+                h_idx == idx
             }
-            None => COLOR_TRANSLUCENT_BLUE,
+            None => false,
         };
 
-        d.draw_ring(
-            center,
-            inner_radius,
-            outer_radius,
-            start_angle,
-            end_angle,
-            0,
-            color,
-        );
+        if highlighted && theme.outline_width > 0.0 {
+            draw_segment(
+                d,
+                &geometry,
+                geometry.outline(idx, theme.outline_width),
+                theme.outline_color,
+            );
+        }
+
+        let segment_color = if highlighted {
+            theme.highlight_color
+        } else {
+            item.color.unwrap_or(theme.segment_color)
+        };
+        draw_segment(d, &geometry, geometry.segment(idx), segment_color);
 
-        draw_icon(
-            d,
-            center,
-            inner_radius,
-            outer_radius,
-            start_angle,
-            end_angle,
-            icon_tex,
-        );
+        let icon_rect = geometry.icon_rect(idx, icon_tex.width() as f32, icon_tex.height() as f32);
+        draw_icon(d, icon_rect, icon_tex);
+
+        if !item.badges.is_empty() {
+            draw_badges(d, font, &item.badges, icon_rect, theme);
+        }
+
+        if theme.segment_labels {
+            draw_label(d, font, &item.name, &geometry, idx, theme);
+        }
 
-        start_angle = end_angle + gap_angle;
+        if highlighted {
+            draw_centre_name(d, font, &item.name, &geometry, theme);
+        }
     }
 
     Ok(())
 }
 
-fn draw_icon(
+/// Raylib's usual letter spacing for a font size
+fn spacing(font_size: f32) -> f32 {
+    font_size / 10.0
+}
+
+/// Shortens `text` with `...` until it is at most `max_width` wide
+fn fit_text(font: &WeakFont, text: &str, font_size: f32, max_width: f32) -> String {
+    let spacing = spacing(font_size);
+    if font.measure_text(text, font_size, spacing).x <= max_width {
+        return text.to_string();
+    }
+
+    let mut chars: Vec<char> = text.chars().collect();
+    while !chars.is_empty() {
+        chars.pop();
+        let shortened = format!("{}...", chars.iter().collect::<String>().trim_end());
+        if font.measure_text(&shortened, font_size, spacing).x <= max_width {
+            return shortened;
+        }
+    }
+
+    String::new()
+}
+
+/// Writes the highlighted entry's name inside the ring
+fn draw_centre_name(
     d: &mut RaylibDrawHandle,
-    center: Vector2,
-    inner_radius: f32,
-    outer_radius: f32,
-    start_angle: f32,
-    end_angle: f32,
-    icon: &Texture2D,
+    font: &WeakFont,
+    name: &str,
+    geometry: &RingGeometry,
+    theme: &Theme,
 ) {
-    let mid_angle = (start_angle + end_angle) / 2.0;
-    let mid_radius = (inner_radius + outer_radius) / 2.0;
-
-    let mid_angle_rad = mid_angle.to_radians();
+    let size = theme.font_size;
+    let text = fit_text(font, name, size, geometry.inner_radius * 1.8);
+    let text_size = font.measure_text(&text, size, spacing(size));
+    let center = vector(geometry.center);
+
+    d.draw_text_ex(
+        font,
+        &text,
+        Vector2::new(center.x - text_size.x / 2.0, center.y - text_size.y / 2.0),
+        size,
+        spacing(size),
+        color(theme.text_color),
+    );
+}
 
-    let icon_pos = Vector2::new(
-        center.x + mid_radius * mid_angle_rad.cos(),
-        center.y + mid_radius * mid_angle_rad.sin(),
+/// Writes `name` just outside the segment of entry `idx`
+fn draw_label(
+    d: &mut RaylibDrawHandle,
+    font: &WeakFont,
+    name: &str,
+    geometry: &RingGeometry,
+    idx: usize,
+    theme: &Theme,
+) {
+    let size = theme.label_font_size;
+    let text = fit_text(font, name, size, geometry.outer_radius);
+    let text_size = font.measure_text(&text, size, spacing(size));
+    let rect = geometry.label_rect(idx, text_size.x, text_size.y);
+
+    d.draw_text_ex(
+        font,
+        &text,
+        Vector2::new(rect.x, rect.y),
+        size,
+        spacing(size),
+        color(theme.text_color),
     );
+}
 
-    let max_icon_size = (outer_radius - inner_radius) * 0.7;
+/// Stacks `badges` downwards from the top right corner of the icon
+fn draw_badges(
+    d: &mut RaylibDrawHandle,
+    font: &WeakFont,
+    badges: &[String],
+    icon_rect: geometry::Rect,
+    theme: &Theme,
+) {
+    let size = theme.label_font_size;
+    let padding = size / 4.0;
+    let mut y = icon_rect.y;
+
+    for badge in badges {
+        let text = fit_text(font, badge, size, icon_rect.width);
+        let text_size = font.measure_text(&text, size, spacing(size));
+        let badge_rect = Rectangle::new(
+            icon_rect.x + icon_rect.width - text_size.x / 2.0 - padding,
+            y - text_size.y / 2.0,
+            text_size.x + padding * 2.0,
+            text_size.y,
+        );
 
-    let icon_width = icon.width() as f32;
-    let icon_height = icon.height() as f32;
-    let scale_factor = if icon_width > icon_height {
-        max_icon_size / icon_width
-    } else {
-        max_icon_size / icon_height
-    };
+        d.draw_rectangle_rounded(badge_rect, 0.5, 8, color(theme.highlight_color));
+        d.draw_text_ex(
+            font,
+            &text,
+            Vector2::new(badge_rect.x + padding, badge_rect.y),
+            size,
+            spacing(size),
+            color(theme.text_color),
+        );
 
-    let scaled_width = icon_width * scale_factor;
-    let scaled_height = icon_height * scale_factor;
+        y += text_size.y + padding;
+    }
+}
 
-    let icon_x = icon_pos.x - (scaled_width / 2.0);
-    let icon_y = icon_pos.y - (scaled_height / 2.0);
+fn draw_segment(d: &mut RaylibDrawHandle, geometry: &RingGeometry, segment: Segment, fill: Rgba) {
+    d.draw_ring(
+        vector(geometry.center),
+        segment.inner_radius,
+        segment.outer_radius,
+        segment.start_angle,
+        segment.end_angle,
+        0,
+        color(fill),
+    );
+}
 
-    let source_rect = Rectangle::new(0.0, 0.0, icon_width, icon_height);
-    let dest_rect = Rectangle::new(icon_x, icon_y, scaled_width, scaled_height);
+/// Draws `icon` stretched over `rect`
+fn draw_icon(d: &mut RaylibDrawHandle, rect: geometry::Rect, icon: &Texture2D) {
+    let source_rect = Rectangle::new(0.0, 0.0, icon.width() as f32, icon.height() as f32);
 
     d.draw_texture_pro(
         icon,
         source_rect,
-        dest_rect,
+        rectangle(rect),
         Vector2::new(0.0, 0.0),
         0.0,
         Color::WHITE,
@@ -0,0 +1,346 @@
+use std::borrow::Cow;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+use directories::ProjectDirs;
+use serde::Deserialize;
+use toml::Spanned;
+use toml::de::{DeTable, DeValue, ValueDeserializer};
+
+use crate::config::{CONFIG_VERSION, Config, config_file_path, pending_migrations};
+use crate::shortcut_parser::{DesktopFile, discover_shortcuts, find_icon};
+use crate::utils::find_in_path;
+
+/// Tables whose keys are checked one by one, so one bad key doesn't hide the others
+const TABLES: &[&str] = &["shortcuts", "bindings", "theme"];
+
+#[derive(Default)]
+struct Report {
+    errors: usize,
+    warnings: usize,
+}
+
+/// 1-based line and column of a byte offset
+fn line_col(contents: &str, offset: usize) -> (usize, usize) {
+    let before = &contents[..offset.min(contents.len())];
+    let line = before.matches('\n').count() + 1;
+    let col = before.len() - before.rfind('\n').map_or(0, |idx| idx + 1) + 1;
+    (line, col)
+}
+
+/// Line of the first `key=` entry in a desktop file
+fn key_line(contents: &str, key: &str) -> Option<(usize, usize)> {
+    contents
+        .lines()
+        .position(|line| {
+            line.strip_prefix(key)
+                .is_some_and(|rest| rest.trim_start().starts_with('='))
+        })
+        .map(|idx| (idx + 1, 1))
+}
+
+impl Report {
+    fn print(&self, severity: &str, file: &Path, pos: Option<(usize, usize)>, msg: &str) {
+        match pos {
+            Some((line, col)) => println!("{}:{line}:{col}: {severity}: {msg}", file.display()),
+            None => println!("{}: {severity}: {msg}", file.display()),
+        }
+    }
+
+    fn error(&mut self, file: &Path, pos: Option<(usize, usize)>, msg: &str) {
+        self.errors += 1;
+        self.print("error", file, pos, msg);
+    }
+
+    fn warning(&mut self, file: &Path, pos: Option<(usize, usize)>, msg: &str) {
+        self.warnings += 1;
+        self.print("warning", file, pos, msg);
+    }
+
+    /// Returns the config to check the shortcuts with, built from the keys without problems
+    fn check_config(&mut self, path: &Path) -> Config {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                self.warning(
+                    path,
+                    None,
+                    &format!("Can't read config, using defaults: {e}"),
+                );
+                return Config::default();
+            }
+        };
+
+        let (root, syntax_errors) = DeTable::parse_recoverable(&contents);
+        for e in &syntax_errors {
+            let pos = e.span().map(|span| line_col(&contents, span.start));
+            self.error(path, pos, e.message().trim());
+        }
+
+        // Each key on its own, on top of the defaults
+        let mut entries: Vec<(Vec<Spanned<_>>, &Spanned<DeValue>)> = Vec::new();
+        for (key, value) in root.get_ref() {
+            match value.get_ref() {
+                DeValue::Table(table) if TABLES.contains(&key.get_ref().as_ref()) => {
+                    entries.extend(
+                        table
+                            .iter()
+                            .map(|(inner, value)| (vec![key.clone(), inner.clone()], value)),
+                    );
+                }
+                _ => entries.push((vec![key.clone()], value)),
+            }
+        }
+        entries.sort_by_key(|(_, value)| value.span().start);
+
+        let mut valid = DeTable::new();
+        for (keys, value) in entries {
+            let single = nest(&keys, value.clone());
+            match check_entry(single.clone()) {
+                Ok(()) => merge(&mut valid, single),
+                Err((span, msg)) => {
+                    let span = span.unwrap_or_else(|| value.span());
+                    self.error(path, Some(line_col(&contents, span.start)), &msg);
+                }
+            }
+        }
+
+        let config = deserialize(valid).unwrap_or_default();
+
+        if config.version > CONFIG_VERSION {
+            let msg = format!(
+                "Version {} is newer than the supported {CONFIG_VERSION}",
+                config.version
+            );
+            self.error(path, None, &msg);
+        } else if let Ok(changes) = pending_migrations(&contents) {
+            for change in changes {
+                self.warning(path, None, &format!("Migrated on the next start: {change}"));
+            }
+        }
+
+        config
+    }
+
+    /// Returns whether the desktop file gives an entry on the ring
+    fn check_desktop_file(&mut self, path: &Path) -> bool {
+        let contents = fs::read_to_string(path).unwrap_or_default();
+
+        // Only parsed, looking up the icons for real would write to the cache dir
+        let desktop_file = match DesktopFile::parse(path) {
+            Ok(Some(desktop_file)) => desktop_file,
+            // Hidden or not installed, not a problem
+            Ok(None) => return false,
+            Err(e) => {
+                let msg = format!("{e:#}");
+                let pos = ["Exec", "Name"]
+                    .iter()
+                    .find(|key| msg.contains(&format!("`{key}`")))
+                    .and_then(|key| key_line(&contents, key));
+                self.error(path, pos, &msg);
+                return false;
+            }
+        };
+
+        match desktop_file.icon_name() {
+            None => self.warning(path, None, "No `Icon` key, the default icon is shown"),
+            Some(icon) if find_icon(icon).is_none() => {
+                let pos = key_line(&contents, "Icon");
+                self.warning(path, pos, &format!("Icon `{icon}` not found"));
+            }
+            Some(_) => {}
+        }
+
+        let mut exec_paths: Vec<&Path> = std::iter::once(&desktop_file)
+            .chain(&desktop_file.actions)
+            .map(DesktopFile::exec_path)
+            .collect();
+        exec_paths.dedup();
+
+        for exec_path in exec_paths {
+            let exec_path = exec_path.to_string_lossy();
+            if find_in_path(&exec_path).is_none() {
+                let pos = key_line(&contents, "Exec");
+                self.warning(
+                    path,
+                    pos,
+                    &format!("`{exec_path}` not found or not executable"),
+                );
+            }
+        }
+
+        true
+    }
+}
+
+/// `value` under `keys`, e.g. `{ bindings = { up = value } }`
+fn nest<'i>(keys: &[Spanned<Cow<'i, str>>], value: Spanned<DeValue<'i>>) -> DeTable<'i> {
+    let (last, parents) = keys.split_last().expect("at least one key");
+    let mut table = DeTable::new();
+    table.insert(last.clone(), value);
+
+    for key in parents.iter().rev() {
+        let span = key.span();
+        let mut outer = DeTable::new();
+        outer.insert(key.clone(), Spanned::new(span, DeValue::Table(table)));
+        table = outer;
+    }
+    table
+}
+
+/// Adds the keys of `other` to `table`, merging tables present in both
+fn merge<'i>(table: &mut DeTable<'i>, other: DeTable<'i>) {
+    for (key, value) in other {
+        match (table.get_mut(key.get_ref().as_ref()), value.into_inner()) {
+            (Some(existing), DeValue::Table(inner)) => {
+                if let DeValue::Table(existing) = existing.get_mut() {
+                    merge(existing, inner);
+                }
+            }
+            (_, value) => {
+                let span = key.span();
+                table.insert(key, Spanned::new(span, value));
+            }
+        }
+    }
+}
+
+fn deserialize(table: DeTable<'_>) -> Result<Config, toml::de::Error> {
+    let root = Spanned::new(0..0, DeValue::Table(table));
+    Config::deserialize(ValueDeserializer::from(root))
+}
+
+/// Checks a config made of a single key, returns where the problem is when known
+fn check_entry(table: DeTable<'_>) -> Result<(), (Option<Range<usize>>, String)> {
+    let config = deserialize(table).map_err(|e| (e.span(), e.message().trim().to_string()))?;
+
+    if let Err(e) = config.validate() {
+        return Err((None, format!("{e:#}")));
+    }
+    if let Some((_, e)) = config.bindings.errors().into_iter().next() {
+        return Err((None, format!("{e:#}")));
+    }
+    Ok(())
+}
+
+/// Loads the config and every shortcut, printing each problem found. Returns `false` when
+/// there are errors, warnings alone still pass
+pub fn run_check(proj_dirs: &ProjectDirs) -> bool {
+    let mut report = Report::default();
+    let config_path = config_file_path(proj_dirs);
+    let config = report.check_config(&config_path);
+
+    let mut desktop_paths = match discover_shortcuts(proj_dirs, &config.shortcuts) {
+        Ok(desktop_paths) => desktop_paths,
+        Err(e) => {
+            report.error(&config_path, None, &format!("{e:#}"));
+            Vec::new()
+        }
+    };
+
+    desktop_paths.sort();
+
+    let mut found_ids = Vec::new();
+    for (id, path) in &desktop_paths {
+        if report.check_desktop_file(path) {
+            found_ids.push(id.as_str());
+        }
+    }
+
+    if found_ids.is_empty() {
+        report.error(&config_path, None, "No usable desktop files found");
+    }
+
+    let listed = config.shortcuts.allow.iter().chain(&config.shortcuts.order);
+    for id in listed.filter(|id| !found_ids.contains(&id.as_str())) {
+        report.warning(
+            &config_path,
+            None,
+            &format!("Desktop file `{id}` not found"),
+        );
+    }
+
+    println!("{} errors, {} warnings", report.errors, report.warnings);
+    report.errors == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    /// Writes `contents` to a file unique to this test run
+    fn write_temp(name: &str, contents: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("aeonium-check-{}-{name}", std::process::id()));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn offsets_map_to_lines_and_columns() {
+        let contents = "a\nbc\nd";
+        assert_eq!(line_col(contents, 0), (1, 1));
+        assert_eq!(line_col(contents, 3), (2, 2));
+        assert_eq!(line_col(contents, 5), (3, 1));
+        assert_eq!(line_col(contents, 100), (3, 2));
+    }
+
+    #[test]
+    fn keys_are_found_at_the_start_of_a_line() {
+        let contents = "[Desktop Entry]\nGenericName=Browser\nName=Files\nExec = nautilus\n";
+        assert_eq!(key_line(contents, "Name"), Some((3, 1)));
+        assert_eq!(key_line(contents, "Exec"), Some((4, 1)));
+        assert_eq!(key_line(contents, "Nam"), None);
+        assert_eq!(key_line(contents, "Icon"), None);
+    }
+
+    #[test]
+    fn every_bad_config_key_is_reported() {
+        let path = write_temp(
+            "config.toml",
+            "version = 1\ntimeout = 250\nunknown = true\n\n[bindings]\nup = \"Ctrl+Nope\"\n\
+             down = \"Ctrl+F9\"\n\n[theme]\nsegment_color = \"red\"\ngap = 90.0\n",
+        );
+
+        let mut report = Report::default();
+        let config = report.check_config(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(report.errors, 4);
+        assert_eq!(report.warnings, 0);
+        // The good keys are still used
+        assert_eq!(config.timeout, 250);
+        assert_eq!(config.bindings.down, "Ctrl+F9");
+    }
+
+    #[test]
+    fn desktop_files_are_checked_without_side_effects() {
+        let valid = write_temp(
+            "valid.desktop",
+            "[Desktop Entry]\nType=Application\nName=Shell\nExec=sh -c true\n",
+        );
+        let no_exec = write_temp("no-exec.desktop", "[Desktop Entry]\nName=Broken\n");
+        let hidden = write_temp(
+            "hidden.desktop",
+            "[Desktop Entry]\nName=Hidden\nExec=sh\nHidden=true\n",
+        );
+
+        let mut report = Report::default();
+        assert!(report.check_desktop_file(&valid));
+        // Only the missing icon
+        assert_eq!((report.errors, report.warnings), (0, 1));
+
+        assert!(!report.check_desktop_file(&no_exec));
+        assert_eq!((report.errors, report.warnings), (1, 1));
+
+        assert!(!report.check_desktop_file(&hidden));
+        assert_eq!((report.errors, report.warnings), (1, 1));
+
+        for path in [valid, no_exec, hidden] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+}
@@ -1,21 +1,206 @@
 use std::fs::{self, File};
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use anyhow::Ok;
+use anyhow::{Context, Ok, bail, ensure};
 use tracing::{warn, info};
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
+use toml_edit::DocumentMut;
+
+use aeonium_menu::theme::Theme;
+
+use crate::key_bind::{KeyBind, KeyBinds, parse_key_list};
+use crate::key_code::KeyMatch;
+use crate::utils::write_atomic;
+
+/// Bumped whenever a key is renamed or changes meaning, together with a new entry in
+/// `MIGRATIONS`
+pub const CONFIG_VERSION: u32 = 1;
+
+/// Upgrades a config from one version to the next, returning a description of every change made
+type Migration = fn(&mut DocumentMut) -> Vec<String>;
+
+/// Migration from the version at its index to the next one
+const MIGRATIONS: &[Migration] = &[
+    // 0 -> 1: configs written before `version` existed, every key kept its meaning
+    |_| Vec::new(),
+];
+
+const _: () = assert!(MIGRATIONS.len() == CONFIG_VERSION as usize);
 
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(default, deny_unknown_fields)]
 pub struct Config {
+    /// Schema version the file was written for. A missing `version` counts as 0 for migrations,
+    /// but the file is only rewritten once one of them changes something
+    pub version: u32,
     pub timeout: u64,
+    /// Read at startup only
+    pub input_backend: InputBackend,
+    pub launch_mode: LaunchMode,
+    /// Idle time in milliseconds before the highlighted entry is launched in `timer` mode
+    pub launch_delay: u64,
+    /// Close the ring without launching when the scroll wheel returns to where it was opened
+    pub cancel_on_scroll_back: bool,
+    /// Pointer movement needed to highlight the segment in its direction, 0 disables it
+    pub flick_dead_zone: u32,
+    /// Touchpad or continuous scroll distance per step, wheels always move one step per detent
+    pub scroll_threshold: f64,
+    /// Command used to run `Terminal=true` entries, e.g. `["alacritty", "-e"]`.
+    /// When empty a known terminal emulator is looked up in `$PATH`
+    pub terminal: Vec<String>,
+    pub shortcuts: Shortcuts,
+    pub bindings: Bindings,
+    pub theme: Theme,
 }
 
 impl Default for Config {
     fn default() -> Self {
-        Config { timeout: 100 }
+        Config {
+            version: CONFIG_VERSION,
+            timeout: 100,
+            input_backend: InputBackend::default(),
+            launch_mode: LaunchMode::default(),
+            launch_delay: 1000,
+            cancel_on_scroll_back: false,
+            flick_dead_zone: 40,
+            scroll_threshold: 15.0,
+            terminal: Vec::new(),
+            shortcuts: Shortcuts::default(),
+            bindings: Bindings::default(),
+            theme: Theme::default(),
+        }
+    }
+}
+
+/// Where key binds and scrolling are read from
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InputBackend {
+    /// Global input through libinput, the user has to be in the `input` group
+    #[default]
+    Libinput,
+    /// No input devices are read, the menu is driven through the control socket only
+    None,
+}
+
+/// When the highlighted entry of an open ring gets launched
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LaunchMode {
+    /// After `launch_delay` milliseconds without input
+    #[default]
+    Timer,
+    /// As soon as a held modifier is released, like alt-tab
+    Release,
+}
+
+/// Where the entries of the ring come from
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Shortcuts {
+    pub discovery: Discovery,
+    /// Desktop file IDs to show, e.g. `firefox.desktop`. Empty shows every entry found
+    pub allow: Vec<String>,
+    /// Desktop file IDs placed first on the ring in this order, the rest are sorted by `sort`
+    pub order: Vec<String>,
+    pub sort: Sort,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sort {
+    #[default]
+    Name,
+    /// Most frequently and recently launched first, launches are recorded in the data dir
+    Frecency,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Discovery {
+    /// `shortcuts/` in the config dir, falling back to `~/.local/share/applications`
+    #[default]
+    Directory,
+    /// Every `applications/` dir under `$XDG_DATA_HOME` and `$XDG_DATA_DIRS`
+    Xdg,
+}
+
+/// Key binds as written by the user, e.g. `up = "Ctrl+Shift+F10"`
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Bindings {
+    /// Keys that must be held for the scroll wheel to move the ring
+    pub scroll: String,
+    pub up: String,
+    pub down: String,
+    /// Closes the ring without launching anything, or goes back from an actions ring.
+    /// An empty string disables it
+    pub cancel: String,
+    /// Opens the desktop actions of the highlighted entry, an empty string disables it
+    pub actions: String,
+    /// Launches the highlighted entry right away, an empty string disables it
+    pub commit: String,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Bindings {
+            scroll: "Ctrl+Shift".to_string(),
+            up: "Ctrl+Shift+F10".to_string(),
+            down: "Ctrl+Shift+F9".to_string(),
+            cancel: "Esc".to_string(),
+            actions: "Ctrl+Shift+F11".to_string(),
+            commit: String::new(),
+        }
+    }
+}
+
+fn parse_bind(action: &str, value: &str) -> anyhow::Result<KeyBind> {
+    value
+        .parse()
+        .with_context(|| format!("Invalid `{action}` binding `{value}`"))
+}
+
+fn parse_optional_bind(action: &str, value: &str) -> anyhow::Result<Option<KeyBind>> {
+    if value.trim().is_empty() {
+        return Ok(None);
+    }
+    parse_bind(action, value).map(Some)
+}
+
+fn parse_modifiers(action: &str, value: &str) -> anyhow::Result<Vec<KeyMatch>> {
+    parse_key_list(value).with_context(|| format!("Invalid `{action}` binding `{value}`"))
+}
+
+impl Bindings {
+    pub fn parse(&self) -> anyhow::Result<KeyBinds> {
+        Ok(KeyBinds {
+            scroll: parse_modifiers("scroll", &self.scroll)?,
+            up: parse_bind("up", &self.up)?,
+            down: parse_bind("down", &self.down)?,
+            cancel: parse_optional_bind("cancel", &self.cancel)?,
+            actions: parse_optional_bind("actions", &self.actions)?,
+            commit: parse_optional_bind("commit", &self.commit)?,
+        })
+    }
+
+    /// Every invalid binding along with its key, where `parse` stops at the first one
+    pub fn errors(&self) -> Vec<(&'static str, anyhow::Error)> {
+        let results = [
+            ("scroll", parse_modifiers("scroll", &self.scroll).err()),
+            ("up", parse_bind("up", &self.up).err()),
+            ("down", parse_bind("down", &self.down).err()),
+            ("cancel", parse_optional_bind("cancel", &self.cancel).err()),
+            ("actions", parse_optional_bind("actions", &self.actions).err()),
+            ("commit", parse_optional_bind("commit", &self.commit).err()),
+        ];
+
+        results
+            .into_iter()
+            .filter_map(|(action, error)| Some((action, error?)))
+            .collect()
     }
 }
 
@@ -28,10 +213,96 @@ fn create_default_config(file_path: &Path) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Version a config was written for, a missing `version` counts as 0
+fn doc_version(doc: &DocumentMut) -> anyhow::Result<u32> {
+    match doc.get("version") {
+        Some(item) => item
+            .as_integer()
+            .and_then(|version| u32::try_from(version).ok())
+            .context("`version` must be a positive integer"),
+        None => Ok(0),
+    }
+}
+
+/// Applies the migrations after the config's version, returning a description of every change.
+/// `version` is only bumped when something changed, so untouched files stay as they are
+fn upgrade(doc: &mut DocumentMut, migrations: &[Migration]) -> anyhow::Result<Vec<String>> {
+    let version = doc_version(doc)?;
+    let latest = migrations.len() as u32;
+
+    if version > latest {
+        bail!(
+            "Config version {version} is newer than the supported version {latest}, \
+             please update aeonium-menu"
+        );
+    }
+
+    let mut changes = Vec::new();
+    for (from, migration) in migrations.iter().enumerate().skip(version as usize) {
+        changes.extend(
+            migration(doc)
+                .into_iter()
+                .map(|change| format!("{from} -> {}: {change}", from + 1)),
+        );
+    }
+
+    if !changes.is_empty() {
+        doc["version"] = toml_edit::value(i64::from(latest));
+    }
+    Ok(changes)
+}
+
+/// Changes the next start would make to a config, without writing anything
+pub fn pending_migrations(config_str: &str) -> anyhow::Result<Vec<String>> {
+    let mut doc: DocumentMut = config_str.parse()?;
+    upgrade(&mut doc, MIGRATIONS)
+}
+
+/// Upgrades an older config, the original is kept next to it as `config.toml.v<version>.bak`.
+/// Returns the contents to parse
+fn migrate(
+    file_path: &Path,
+    config_str: String,
+    migrations: &[Migration],
+) -> anyhow::Result<String> {
+    let mut doc: DocumentMut = config_str
+        .parse()
+        .with_context(|| format!("Failed to parse {}", file_path.display()))?;
+    let version = doc_version(&doc)?;
+
+    let changes = upgrade(&mut doc, migrations)?;
+    if changes.is_empty() {
+        return Ok(config_str);
+    }
+    for change in &changes {
+        info!("Config migration {change}");
+    }
+
+    let backup_path = file_path.with_extension(format!("toml.v{version}.bak"));
+    fs::copy(file_path, &backup_path)
+        .with_context(|| format!("Failed to back up config to {}", backup_path.display()))?;
+
+    let migrated = doc.to_string();
+    write_atomic(file_path, &migrated)
+        .with_context(|| format!("Failed to write migrated config {}", file_path.display()))?;
+
+    warn!(
+        "Migrated config from version {version} to {}, the old one was saved as {}",
+        migrations.len(),
+        backup_path.display()
+    );
+
+    Ok(migrated)
+}
+
+pub fn config_file_path(proj_dirs: &ProjectDirs) -> PathBuf {
+    proj_dirs.config_dir().join("config.toml")
+}
+
 impl Config {
     pub fn parse(proj_dirs: &ProjectDirs) -> anyhow::Result<Self> {
         let config_dir = proj_dirs.config_dir();
-        let config_file_path = config_dir.join("config.toml");
+        let config_file_path = config_file_path(proj_dirs);
 
         if !config_dir.is_dir() {
             info!("Config dir doesn't exit, creating...");
@@ -43,9 +314,125 @@ impl Config {
             create_default_config(&config_file_path)?;
         }
 
-        let config_str = fs::read_to_string(config_file_path)?;
-        let config_vals = toml::from_str(&config_str)?;
+        let config_str = fs::read_to_string(&config_file_path)?;
+        let config_str = migrate(&config_file_path, config_str, MIGRATIONS)?;
+        let config_vals: Config = toml::from_str(&config_str)?;
+        config_vals.validate()?;
 
         Ok(config_vals)
     }
+
+    /// Checks the values serde can't, such as ranges
+    pub fn validate(&self) -> anyhow::Result<()> {
+        ensure!(
+            self.scroll_threshold > 0.0,
+            "`scroll_threshold` must be positive, got {}",
+            self.scroll_threshold
+        );
+        self.theme.validate()
+    }
+
+    /// Reads the config without creating, migrating or validating anything, the default config
+    /// stands in for a missing file
+    pub fn read(proj_dirs: &ProjectDirs) -> anyhow::Result<Self> {
+        let config_file_path = config_file_path(proj_dirs);
+        if !config_file_path.is_file() {
+            return Ok(Config::default());
+        }
+
+        let config_str = fs::read_to_string(&config_file_path)?;
+        Ok(toml::from_str(&config_str)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stands in for a future migration, renames `timeout` to `poll_interval`
+    fn rename_timeout(doc: &mut DocumentMut) -> Vec<String> {
+        match doc.remove("timeout") {
+            Some(timeout) => {
+                doc["poll_interval"] = timeout;
+                vec!["Renamed `timeout` to `poll_interval`".to_string()]
+            }
+            None => Vec::new(),
+        }
+    }
+
+    const TEST_MIGRATIONS: &[Migration] = &[|_| Vec::new(), rename_timeout];
+
+    fn upgraded(config_str: &str) -> (String, Vec<String>) {
+        let mut doc: DocumentMut = config_str.parse().unwrap();
+        let changes = upgrade(&mut doc, TEST_MIGRATIONS).unwrap();
+        (doc.to_string(), changes)
+    }
+
+    #[test]
+    fn configs_without_changes_are_left_alone() {
+        assert_eq!(upgraded("timeout = 5\n").1.len(), 1);
+        assert_eq!(
+            upgraded("launch_delay = 5\n"),
+            ("launch_delay = 5\n".to_string(), Vec::new())
+        );
+        assert!(pending_migrations("timeout = 5\n").unwrap().is_empty());
+    }
+
+    #[test]
+    fn migrations_run_from_the_config_version() {
+        let (migrated, changes) = upgraded("# Comments survive\nlaunch_delay = 5\ntimeout = 5\n");
+        assert_eq!(changes, ["1 -> 2: Renamed `timeout` to `poll_interval`"]);
+        assert!(migrated.contains("# Comments survive"));
+        assert!(migrated.contains("poll_interval = 5"));
+        assert!(migrated.contains("version = 2"));
+
+        // Already past the rename
+        assert!(upgraded("version = 2\ntimeout = 5\n").1.is_empty());
+    }
+
+    #[test]
+    fn newer_or_invalid_versions_are_rejected() {
+        for config_str in ["version = 3", "version = -1", "version = \"1\""] {
+            let mut doc: DocumentMut = config_str.parse().unwrap();
+            assert!(upgrade(&mut doc, TEST_MIGRATIONS).is_err(), "{config_str}");
+        }
+    }
+
+    #[test]
+    fn scroll_threshold_must_be_positive() {
+        let config: Config = toml::from_str("scroll_threshold = 7.5").unwrap();
+        assert!(config.validate().is_ok());
+
+        for config_str in ["scroll_threshold = 0.0", "scroll_threshold = -15.0"] {
+            let config: Config = toml::from_str(config_str).unwrap();
+            assert!(config.validate().is_err(), "{config_str}");
+        }
+
+        // Only read at the top level
+        assert!(toml::from_str::<Config>("[bindings]\nscroll_threshold = 15.0").is_err());
+    }
+
+    #[test]
+    fn migrated_files_are_backed_up() {
+        let dir = std::env::temp_dir().join(format!("aeonium-migrate-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file_path = dir.join("config.toml");
+
+        let write_and_migrate = |config_str: &str| {
+            fs::write(&file_path, config_str).unwrap();
+            migrate(&file_path, config_str.to_string(), TEST_MIGRATIONS).unwrap()
+        };
+
+        write_and_migrate("launch_delay = 5\n");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        let migrated = write_and_migrate("version = 1\ntimeout = 5\n");
+        let backup = fs::read_to_string(dir.join("config.toml.v1.bak")).unwrap();
+        let written = fs::read_to_string(&file_path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(backup, "version = 1\ntimeout = 5\n");
+        assert_eq!(written, migrated);
+        assert_eq!(written, "version = 2\npoll_interval = 5\n");
+    }
 }
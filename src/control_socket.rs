@@ -0,0 +1,246 @@
+use std::fs::{DirBuilder, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::net::Shutdown;
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::Once;
+use std::sync::mpsc::Sender;
+use std::{env, fs, process, thread};
+
+use anyhow::{Context, bail, ensure};
+use tracing::{info, warn};
+
+use crate::gui_state::EventType;
+
+/// `$XDG_RUNTIME_DIR`, or a private `/tmp/aeonium-<uid>` for sessions that don't set it
+fn runtime_dir() -> anyhow::Result<PathBuf> {
+    if let Some(dir) = env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        return Ok(PathBuf::from(dir));
+    }
+
+    // Safety: getuid always succeeds
+    let uid = unsafe { libc::getuid() };
+    let dir = PathBuf::from(format!("/tmp/aeonium-{uid}"));
+
+    static WARN: Once = Once::new();
+    WARN.call_once(|| warn!("XDG_RUNTIME_DIR not set, using {}", dir.display()));
+
+    if let Err(e) = DirBuilder::new().mode(0o700).create(&dir)
+        && e.kind() != std::io::ErrorKind::AlreadyExists
+    {
+        return Err(e).with_context(|| format!("Failed to create {}", dir.display()));
+    }
+
+    // Anyone can create it in /tmp first, so make sure nobody else can use it
+    let meta =
+        fs::symlink_metadata(&dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+    ensure!(
+        meta.is_dir() && meta.uid() == uid && meta.mode() & 0o077 == 0,
+        "{} is not a private directory owned by the current user",
+        dir.display()
+    );
+
+    Ok(dir)
+}
+
+/// Path of the control socket, `$XDG_RUNTIME_DIR/aeonium.sock`
+pub fn socket_path() -> anyhow::Result<PathBuf> {
+    Ok(runtime_dir()?.join("aeonium.sock"))
+}
+
+/// Takes an exclusive lock on `aeonium.lock` next to the socket, held until the returned file is
+/// dropped. Fails when another instance holds it
+pub fn lock_instance() -> anyhow::Result<File> {
+    let path = runtime_dir()?.join("aeonium.lock");
+
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open lock file {}", path.display()))?;
+
+    // Safety: the fd belongs to `file` which is alive for the whole call
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        bail!("Another instance of aeonium-menu is already running");
+    }
+
+    Ok(file)
+}
+
+/// Sends one command to the running instance and returns the text after its `ok`
+pub fn send_command(command: &str) -> anyhow::Result<String> {
+    let path = socket_path()?;
+    let mut stream = UnixStream::connect(&path)
+        .with_context(|| format!("No running instance found at {}", path.display()))?;
+
+    writeln!(stream, "{command}").context("Failed to send command")?;
+    stream.shutdown(Shutdown::Write)?;
+
+    let mut reply = String::new();
+    BufReader::new(stream)
+        .read_line(&mut reply)
+        .context("Failed to read reply")?;
+    let reply = reply.trim();
+
+    match reply.strip_prefix("error: ") {
+        Some(e) => bail!("{e}"),
+        None => Ok(reply.strip_prefix("ok").unwrap_or(reply).trim().to_string()),
+    }
+}
+
+/// Parses one line sent to the control socket
+pub fn parse_command(line: &str) -> anyhow::Result<EventType> {
+    let mut words = line.split_whitespace();
+    let command = words.next().unwrap_or_default().to_lowercase();
+
+    let event = match command.as_str() {
+        "show" => EventType::Show,
+        "next" => EventType::MenuUp,
+        "prev" => EventType::MenuDown,
+        "select" => {
+            let idx = words
+                .next()
+                .context("`select` expects an entry index")?
+                .parse()
+                .context("`select` expects an entry index")?;
+            EventType::Select(idx)
+        }
+        "launch" => EventType::Commit,
+        "cancel" => EventType::Cancel,
+        "reload" => EventType::Reload,
+        "" => bail!("Empty command"),
+        other => bail!("Unknown command `{other}`"),
+    };
+
+    if words.next().is_some() {
+        bail!("Too many arguments for `{command}`");
+    }
+
+    Ok(event)
+}
+
+fn handle_client(stream: UnixStream, tx: &Sender<EventType>) -> anyhow::Result<()> {
+    let mut writer = stream.try_clone().context("Failed to clone client stream")?;
+
+    for line in BufReader::new(stream).lines() {
+        let line = line.context("Failed to read from client")?;
+
+        // Answered here, the main loop only has to deal with events
+        if line.trim().eq_ignore_ascii_case("status") {
+            writeln!(writer, "ok running, pid {}", process::id())?;
+            continue;
+        }
+
+        match parse_command(&line) {
+            Ok(event) => {
+                tx.send(event).context("Failed to forward socket command")?;
+                writeln!(writer, "ok")?;
+            }
+            Err(e) => {
+                warn!("Rejected socket command `{}`: {e}", line.trim());
+                writeln!(writer, "error: {e}")?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The control socket's file, removed again when dropped
+pub struct SocketFile(PathBuf);
+
+impl Drop for SocketFile {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.0) {
+            warn!("Failed to remove control socket {}: {e}", self.0.display());
+        }
+    }
+}
+
+/// Listens on the control socket, replacing a stale socket file left behind by a crash
+pub fn bind_control_socket() -> anyhow::Result<(UnixListener, SocketFile)> {
+    let path = socket_path()?;
+
+    if path.exists() {
+        if UnixStream::connect(&path).is_ok() {
+            bail!(
+                "Control socket {} is in use by another instance",
+                path.display()
+            );
+        }
+        fs::remove_file(&path)
+            .with_context(|| format!("Failed to remove stale socket {}", path.display()))?;
+    }
+
+    let listener = UnixListener::bind(&path)
+        .with_context(|| format!("Failed to bind control socket {}", path.display()))?;
+    info!("Listening for commands on {}", path.display());
+
+    Ok((listener, SocketFile(path)))
+}
+
+/// Accepts commands such as `show`, `next`, `prev`, `select <n>`, `launch`, `cancel`, `reload`
+/// and `status` on the control socket, one per line, and forwards them like input events
+pub fn run_control_socket(listener: UnixListener, tx: Sender<EventType>) {
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    if let Err(e) = handle_client(stream, &tx) {
+                        warn!("Control socket client error: {:?}", e);
+                    }
+                });
+            }
+            Err(e) => warn!("Failed to accept control socket client: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> EventType {
+        parse_command(line).unwrap()
+    }
+
+    #[test]
+    fn every_verb_maps_to_its_event() {
+        assert!(matches!(parse("show"), EventType::Show));
+        assert!(matches!(parse("next"), EventType::MenuUp));
+        assert!(matches!(parse("prev"), EventType::MenuDown));
+        assert!(matches!(parse("launch"), EventType::Commit));
+        assert!(matches!(parse("cancel"), EventType::Cancel));
+        assert!(matches!(parse("reload"), EventType::Reload));
+    }
+
+    #[test]
+    fn commands_ignore_case_and_surrounding_whitespace() {
+        assert!(matches!(parse("  NEXT\n"), EventType::MenuUp));
+        assert!(matches!(parse("Select   3"), EventType::Select(3)));
+    }
+
+    #[test]
+    fn select_expects_one_index() {
+        assert!(matches!(parse("select 0"), EventType::Select(0)));
+        assert!(parse_command("select").is_err());
+        assert!(parse_command("select -1").is_err());
+        assert!(parse_command("select two").is_err());
+        assert!(parse_command("select 99999999999999999999999").is_err());
+        assert!(parse_command("select 1 2").is_err());
+    }
+
+    #[test]
+    fn unknown_commands_are_rejected() {
+        assert!(parse_command("").is_err());
+        assert!(parse_command("   ").is_err());
+        assert!(parse_command("explode").is_err());
+        assert!(parse_command("next now").is_err());
+        // Answered by the socket thread itself, never turned into an event
+        assert!(parse_command("status").is_err());
+    }
+}
@@ -0,0 +1,323 @@
+//! Layout of the ring, shared by everything that draws it or maps points onto it.
+//!
+//! Angles are in degrees, clockwise from the positive x axis as on screen. The first segment
+//! starts at the top and the segments follow clockwise, each followed by a gap.
+
+use crate::theme::Theme;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Point {
+    pub const fn new(x: f32, y: f32) -> Self {
+        Point { x, y }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+/// Part of an annulus between two angles
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Segment {
+    pub inner_radius: f32,
+    pub outer_radius: f32,
+    pub start_angle: f32,
+    pub end_angle: f32,
+}
+
+impl Segment {
+    pub fn mid_angle(&self) -> f32 {
+        (self.start_angle + self.end_angle) / 2.0
+    }
+
+    pub fn mid_radius(&self) -> f32 {
+        (self.inner_radius + self.outer_radius) / 2.0
+    }
+}
+
+/// Where the segments of a ring with a given number of entries end up
+#[derive(Debug, Clone, PartialEq)]
+pub struct RingGeometry {
+    pub center: Point,
+    pub inner_radius: f32,
+    pub outer_radius: f32,
+    pub segments: usize,
+    /// Empty angle after each segment
+    pub gap_angle: f32,
+    /// Angle covered by each segment
+    pub segment_angle: f32,
+    /// Largest side of an icon
+    pub icon_size: f32,
+}
+
+/// Angle the first segment starts at, straight up
+const START_ANGLE: f32 = -90.0;
+
+impl RingGeometry {
+    /// Lays out `segments` entries on a `width` x `height` screen
+    pub fn new(width: f32, height: f32, segments: usize, theme: &Theme) -> Self {
+        let outer_radius = height.min(width) * theme.outer_radius;
+        let inner_radius = outer_radius * theme.inner_radius;
+
+        // Keep at least half of the ring for the segments when there are many of them
+        let gap_angle = theme.gap.min(180.0 / segments.max(1) as f32);
+        let segment_angle = 360.0 / segments.max(1) as f32 - gap_angle;
+
+        RingGeometry {
+            center: Point::new(width / 2.0, height / 2.0),
+            inner_radius,
+            outer_radius,
+            segments,
+            gap_angle,
+            segment_angle,
+            icon_size: (outer_radius - inner_radius) * theme.icon_scale,
+        }
+    }
+
+    /// Segment of entry `idx`
+    pub fn segment(&self, idx: usize) -> Segment {
+        let start_angle = START_ANGLE + idx as f32 * (self.segment_angle + self.gap_angle);
+
+        Segment {
+            inner_radius: self.inner_radius,
+            outer_radius: self.outer_radius,
+            start_angle,
+            end_angle: start_angle + self.segment_angle,
+        }
+    }
+
+    /// Segment of every entry in order
+    pub fn segments(&self) -> impl Iterator<Item = Segment> + '_ {
+        (0..self.segments).map(|idx| self.segment(idx))
+    }
+
+    /// Segment `width` larger than entry `idx` on all sides, drawn behind it as an outline
+    pub fn outline(&self, idx: usize, width: f32) -> Segment {
+        let segment = self.segment(idx);
+        // Widen the angles so the outline is as thick at the sides as at the edges
+        let angle_pad = (width / self.inner_radius.max(1.0)).to_degrees();
+
+        Segment {
+            inner_radius: (segment.inner_radius - width).max(0.0),
+            outer_radius: segment.outer_radius + width,
+            start_angle: segment.start_angle - angle_pad,
+            end_angle: segment.end_angle + angle_pad,
+        }
+    }
+
+    /// Point at `radius` from the centre in the direction of `angle`
+    pub fn point(&self, radius: f32, angle: f32) -> Point {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        Point::new(self.center.x + radius * cos, self.center.y + radius * sin)
+    }
+
+    /// Where an icon of the given size goes for entry `idx`, scaled to fit and centered in the
+    /// middle of its segment
+    pub fn icon_rect(&self, idx: usize, icon_width: f32, icon_height: f32) -> Rect {
+        let segment = self.segment(idx);
+        let center = self.point(segment.mid_radius(), segment.mid_angle());
+
+        let scale = self.icon_size / icon_width.max(icon_height);
+        let (width, height) = (icon_width * scale, icon_height * scale);
+
+        Rect {
+            x: center.x - width / 2.0,
+            y: center.y - height / 2.0,
+            width,
+            height,
+        }
+    }
+
+    /// Where a label of the given size goes for entry `idx`, just outside the ring and pushed
+    /// away from it in the direction of the segment
+    pub fn label_rect(&self, idx: usize, text_width: f32, text_height: f32) -> Rect {
+        let mid_angle = self.segment(idx).mid_angle();
+        let anchor = self.point(self.outer_radius + text_height / 2.0, mid_angle);
+        let (sin, cos) = mid_angle.to_radians().sin_cos();
+
+        // Move the text box outwards so its nearest edge touches the anchor
+        Rect {
+            x: anchor.x - text_width / 2.0 + cos * text_width / 2.0,
+            y: anchor.y - text_height / 2.0 + sin * text_height / 2.0,
+            width: text_width,
+            height: text_height,
+        }
+    }
+
+    /// Entry whose segment covers `angle`, `None` when it falls into a gap
+    pub fn segment_at_angle(&self, angle: f32) -> Option<usize> {
+        if self.segments == 0 {
+            return None;
+        }
+
+        let step = self.segment_angle + self.gap_angle;
+        let offset = (angle - START_ANGLE).rem_euclid(360.0);
+        let idx = ((offset / step) as usize).min(self.segments - 1);
+
+        (offset - idx as f32 * step <= self.segment_angle).then_some(idx)
+    }
+
+    /// Entry whose segment is closest to `angle`, a gap counts towards the nearer neighbour.
+    /// `None` only without any entries
+    pub fn segment_towards(&self, angle: f32) -> Option<usize> {
+        if self.segments == 0 {
+            return None;
+        }
+
+        let step = self.segment_angle + self.gap_angle;
+        let offset = (angle - START_ANGLE).rem_euclid(360.0);
+        let idx = ((offset / step) as usize).min(self.segments - 1);
+
+        if offset - idx as f32 * step - self.segment_angle <= self.gap_angle / 2.0 {
+            Some(idx)
+        } else {
+            Some((idx + 1) % self.segments)
+        }
+    }
+
+    /// Entry whose segment contains `point`, `None` inside the ring, outside of it or in a gap
+    pub fn segment_at(&self, point: Point) -> Option<usize> {
+        let (dx, dy) = (point.x - self.center.x, point.y - self.center.y);
+        let radius = dx.hypot(dy);
+
+        if radius < self.inner_radius || radius > self.outer_radius {
+            return None;
+        }
+        self.segment_at_angle(dy.atan2(dx).to_degrees())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-3,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    /// 4 entries on a 400x400 screen, the ring spans radii 75 to 100 around (200, 200)
+    fn four_segments() -> RingGeometry {
+        RingGeometry::new(400.0, 400.0, 4, &Theme::default())
+    }
+
+    #[test]
+    fn segments_start_at_the_top_and_follow_clockwise() {
+        let geometry = four_segments();
+        assert_close(geometry.outer_radius, 100.0);
+        assert_close(geometry.inner_radius, 75.0);
+
+        let segments: Vec<Segment> = geometry.segments().collect();
+        assert_eq!(segments.len(), 4);
+        assert_close(segments[0].start_angle, -90.0);
+        assert_close(segments[0].end_angle, -2.0);
+        assert_close(segments[1].start_angle, 0.0);
+        assert_close(segments[3].end_angle, 268.0);
+    }
+
+    #[test]
+    fn gaps_shrink_for_many_segments() {
+        let theme = Theme {
+            gap: 10.0,
+            ..Theme::default()
+        };
+        let geometry = RingGeometry::new(400.0, 400.0, 36, &theme);
+        assert_close(geometry.gap_angle, 5.0);
+        assert_close(geometry.segment_angle, 5.0);
+    }
+
+    #[test]
+    fn icons_fit_in_the_middle_of_their_segment() {
+        let geometry = four_segments();
+        // 25 wide ring at the default icon scale of 0.7
+        assert_close(geometry.icon_size, 17.5);
+
+        let rect = geometry.icon_rect(1, 64.0, 32.0);
+        assert_close(rect.width, 17.5);
+        assert_close(rect.height, 8.75);
+
+        let mid = geometry.point(87.5, 44.0);
+        assert_close(rect.x + rect.width / 2.0, mid.x);
+        assert_close(rect.y + rect.height / 2.0, mid.y);
+    }
+
+    #[test]
+    fn labels_sit_outside_the_ring() {
+        let geometry = four_segments();
+        // The second segment points down and to the right, so the label grows that way
+        let rect = geometry.label_rect(1, 40.0, 10.0);
+        let anchor = geometry.point(105.0, 44.0);
+        assert!(rect.x > anchor.x - 20.0 && rect.y > anchor.y - 5.0);
+        assert_close(rect.width, 40.0);
+    }
+
+    #[test]
+    fn outline_surrounds_the_segment() {
+        let geometry = four_segments();
+        let outline = geometry.outline(0, 2.0);
+        let segment = geometry.segment(0);
+        assert_close(outline.inner_radius, 73.0);
+        assert_close(outline.outer_radius, 102.0);
+        assert!(outline.start_angle < segment.start_angle);
+        assert!(outline.end_angle > segment.end_angle);
+    }
+
+    #[test]
+    fn angles_map_to_segments() {
+        let geometry = four_segments();
+        assert_eq!(geometry.segment_at_angle(-90.0), Some(0));
+        assert_eq!(geometry.segment_at_angle(-45.0), Some(0));
+        assert_eq!(geometry.segment_at_angle(-1.0), None);
+        assert_eq!(geometry.segment_at_angle(45.0), Some(1));
+        assert_eq!(geometry.segment_at_angle(135.0), Some(2));
+        assert_eq!(geometry.segment_at_angle(180.0), Some(3));
+        // Same angle one turn later or earlier
+        assert_eq!(geometry.segment_at_angle(225.0), Some(3));
+        assert_eq!(geometry.segment_at_angle(-135.0), Some(3));
+        assert_eq!(geometry.segment_at_angle(269.0), None);
+    }
+
+    #[test]
+    fn gaps_snap_to_the_nearest_segment() {
+        let geometry = four_segments();
+        assert_eq!(geometry.segment_towards(-45.0), Some(0));
+        // The gap between the first and second segment spans -2 to 0
+        assert_eq!(geometry.segment_towards(-1.5), Some(0));
+        assert_eq!(geometry.segment_towards(-0.5), Some(1));
+        // The last gap wraps around to the first segment
+        assert_eq!(geometry.segment_towards(-90.5), Some(0));
+        assert_eq!(geometry.segment_towards(-91.5), Some(3));
+    }
+
+    #[test]
+    fn points_map_to_segments() {
+        let geometry = four_segments();
+        assert_eq!(geometry.segment_at(Point::new(260.0, 140.0)), Some(0));
+        assert_eq!(geometry.segment_at(Point::new(260.0, 260.0)), Some(1));
+        assert_eq!(geometry.segment_at(Point::new(140.0, 260.0)), Some(2));
+        assert_eq!(geometry.segment_at(Point::new(200.0, 290.0)), Some(2));
+        // The centre, beyond the ring and a gap
+        assert_eq!(geometry.segment_at(Point::new(200.0, 200.0)), None);
+        assert_eq!(geometry.segment_at(Point::new(380.0, 200.0)), None);
+        assert_eq!(geometry.segment_at(Point::new(198.5, 113.0)), None);
+    }
+
+    #[test]
+    fn empty_ring_has_no_segments() {
+        let geometry = RingGeometry::new(400.0, 400.0, 0, &Theme::default());
+        assert_eq!(geometry.segments().count(), 0);
+        assert_eq!(geometry.segment_at_angle(0.0), None);
+        assert_eq!(geometry.segment_towards(0.0), None);
+    }
+}
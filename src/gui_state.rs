@@ -1,20 +1,47 @@
 use std::cmp::Ordering;
-use std::io::Write;
+use std::io::Read;
+use std::os::fd::AsFd;
 use std::path::PathBuf;
-use std::process::{Child, ChildStdin, Command, Stdio};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
 use std::time::{Duration, Instant};
 
-use anyhow::Context;
+use anyhow::{Context, bail};
 use directories::ProjectDirs;
-use tracing::info;
+use nix::poll::{PollFd, PollFlags, PollTimeout, poll};
+use tracing::{error, info, warn};
 
-use crate::shortcut_parser::DesktopFile;
+use aeonium_menu::geometry::RingGeometry;
+use aeonium_menu::protocol::{self, PROTOCOL_VERSION, Reply, read_message, write_message};
+use aeonium_menu::theme::Theme;
+
+use crate::config::{Config, LaunchMode, Sort};
+use crate::history::History;
+use crate::shortcut_parser::{DesktopFile, find_terminal};
+
+/// How long the GUI gets to answer `hello` before it is considered hung
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[derive(Debug)]
+#[cfg_attr(not(feature = "libinput"), allow(dead_code))]
 pub enum EventType {
     MenuUp,
     MenuDown,
     Scroll(i32),
+    ModifiersReleased,
+    Cancel,
+    OpenActions,
+    /// Opens the ring without moving the highlight
+    Show,
+    /// Opens the ring if needed and highlights the entry at this index
+    Select(usize),
+    /// Launches the highlighted entry right away
+    Commit,
+    /// Reloads the config and shortcuts once the ring is closed
+    Reload,
+    /// Relative pointer movement while the scroll modifiers are held
+    PointerMotion(f64, f64),
+    /// Closes the ring before the main loop stops
+    Quit,
 }
 
 pub struct GuiState {
@@ -23,102 +50,404 @@ pub struct GuiState {
     highlight_idx: Option<usize>,
     idle_duration: Option<Instant>,
     gui_bin_path: PathBuf,
+    launch_mode: LaunchMode,
+    launch_delay: Duration,
+    cancel_on_scroll_back: bool,
+    /// Net scroll steps since the ring was opened
+    scroll_offset: i32,
+    flick_dead_zone: f64,
+    /// Pointer movement since the ring was opened, never further than `flick_dead_zone` out
+    pointer_offset: (f64, f64),
+    terminal: Option<Vec<String>>,
+    /// Indices of the entries whose actions are shown, outermost first
+    menu_stack: Vec<usize>,
+    sort: Sort,
+    history: History,
+    /// Set when a launch was added to `history`, until the main loop re-sorts the entries
+    launch_recorded: bool,
+    theme: Theme,
+}
+
+/// Describes the entries of a ring to the GUI
+fn protocol_items(desktop_files: &[DesktopFile]) -> Vec<protocol::Item> {
+    desktop_files
+        .iter()
+        .map(|desktop_file| protocol::Item {
+            name: desktop_file.name.clone(),
+            icon: desktop_file.icon.clone(),
+            color: None,
+            badges: Vec::new(),
+        })
+        .collect()
 }
 
 impl GuiState {
-    pub fn new(proj_dirs: &ProjectDirs) -> Self {
-        GuiState {
+    pub fn new(proj_dirs: &ProjectDirs, config: &Config, history: History) -> Self {
+        let mut gui_state = GuiState {
             gui_process: None,
             gui_stdin: None,
             highlight_idx: None,
             idle_duration: None,
-            gui_bin_path: proj_dirs.data_dir().join("aeonium-gui")
+            gui_bin_path: proj_dirs.data_dir().join("aeonium-gui"),
+            launch_mode: LaunchMode::default(),
+            launch_delay: Duration::ZERO,
+            cancel_on_scroll_back: false,
+            scroll_offset: 0,
+            flick_dead_zone: 0.0,
+            pointer_offset: (0.0, 0.0),
+            terminal: None,
+            menu_stack: Vec::new(),
+            sort: Sort::default(),
+            history,
+            launch_recorded: false,
+            theme: Theme::default(),
+        };
+        gui_state.apply_config(config);
+        gui_state
+    }
+
+    /// Takes over the settings of a freshly loaded config
+    pub fn apply_config(&mut self, config: &Config) {
+        let terminal = find_terminal(&config.terminal);
+        match &terminal {
+            Some(cmd) => info!("Using terminal command {:?}", cmd),
+            None => warn!("No terminal emulator found, `Terminal=true` entries can't be launched"),
+        }
+
+        self.launch_mode = config.launch_mode;
+        self.launch_delay = Duration::from_millis(config.launch_delay);
+        self.cancel_on_scroll_back = config.cancel_on_scroll_back;
+        self.flick_dead_zone = config.flick_dead_zone as f64;
+        self.terminal = terminal;
+        self.sort = config.shortcuts.sort;
+        self.theme = config.theme.clone();
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.gui_process.is_some()
+    }
+
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// Whether a launch was recorded since the last call, the frecency order is stale then
+    pub fn take_launch_recorded(&mut self) -> bool {
+        std::mem::take(&mut self.launch_recorded)
+    }
+
+    /// Index of the most used entry when sorting by frecency
+    fn most_used(&self, shortcut_files: &[DesktopFile]) -> Option<usize> {
+        if self.sort != Sort::Frecency {
+            return None;
         }
+
+        shortcut_files
+            .iter()
+            .map(|desktop_file| self.history.frecency(&desktop_file.id))
+            .enumerate()
+            .filter(|&(_, score)| score > 0.0)
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(idx, _)| idx)
     }
 
     pub fn tick(
         &mut self,
         event: Option<EventType>,
-        segments: usize,
         shortcut_files: &[DesktopFile],
     ) -> anyhow::Result<()> {
         match event {
             Some(event) => {
-                self.handle_event(event, segments, shortcut_files)?;
+                // Motion arrives far more often than the highlight changes
+                let motion = matches!(event, EventType::PointerMotion(..));
+                let previous_idx = self.highlight_idx;
+
+                self.handle_event(event, shortcut_files)?;
 
-                if let (Some(stdin), Some(idx)) = (self.gui_stdin.as_mut(), self.highlight_idx) {
-                    writeln!(stdin, "HIGHLIGHT {}", idx).context("Failed to write GUI stdin")?;
-                    stdin.flush().context("Failed to flush stdin")?;
+                if motion && self.highlight_idx == previous_idx {
+                    return Ok(());
+                }
+                if let Some(index) = self.highlight_idx {
+                    self.send(&protocol::Command::Highlight { index })?;
                 }
                 Ok(())
             }
 
             None => {
-                if let (Some(start), Some(stdin), Some(idx)) = (
-                    self.idle_duration,
-                    self.gui_stdin.as_mut(),
-                    self.highlight_idx,
-                ) {
-                    if start.elapsed() > Duration::from_secs(1) {
-                        writeln!(stdin, "QUIT").context("Failed to write to GUI stdin")?;
-                        self.idle_duration = None;
-                        shortcut_files[idx].spawn_process()?;
-
-                        if let Some(mut child) = self.gui_process.take() {
-                            let status = child.wait().context("GUI process wasn't running")?;
-
-                            match status.code() {
-                                Some(code) => info!("GUI process exited with status code: {code}"),
-                                None => info!("GUI process terminated by signal"),
-                            }
-                        }
-                        self.gui_stdin = None;
-                    }
+                if self.launch_mode != LaunchMode::Timer {
+                    return Ok(());
+                }
+
+                if let Some(start) = self.idle_duration
+                    && start.elapsed() > self.launch_delay
+                {
+                    self.launch_highlighted(shortcut_files)?;
                 }
                 Ok(())
             }
         }
     }
 
+    /// Entries of the ring that is currently shown, walking down the menu stack
+    fn current_items<'a>(&self, shortcut_files: &'a [DesktopFile]) -> &'a [DesktopFile] {
+        self.menu_stack
+            .iter()
+            .fold(shortcut_files, |items, &idx| &items[idx].actions)
+    }
+
+    /// Closes the GUI and spawns the currently highlighted entry
+    fn launch_highlighted(&mut self, shortcut_files: &[DesktopFile]) -> anyhow::Result<()> {
+        let Some(idx) = self.highlight_idx else {
+            return Ok(());
+        };
+
+        if self.gui_stdin.is_none() {
+            return Ok(());
+        }
+
+        let items = self.current_items(shortcut_files);
+        // Actions count as launches of the entry they belong to
+        let top_level_idx = self.menu_stack.first().copied().unwrap_or(idx);
+
+        self.close_gui()?;
+        // A broken entry must not take the menu down with it
+        if let Err(e) = items[idx].spawn_process(self.terminal.as_deref()) {
+            error!("Launch failed: {:?}", e);
+            return Ok(());
+        }
+
+        if self.sort == Sort::Frecency {
+            match self.history.record(&shortcut_files[top_level_idx].id) {
+                Ok(()) => self.launch_recorded = true,
+                Err(e) => warn!("Failed to record launch: {:?}", e),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Tells the GUI to quit and reaps it, does nothing if it isn't running
+    fn close_gui(&mut self) -> anyhow::Result<()> {
+        self.idle_duration = None;
+        self.scroll_offset = 0;
+        self.pointer_offset = (0.0, 0.0);
+        self.menu_stack.clear();
+
+        self.send(&protocol::Command::Close)?;
+        self.gui_stdin = None;
+
+        if let Some(mut child) = self.gui_process.take() {
+            let status = child.wait().context("GUI process wasn't running")?;
+
+            match status.code() {
+                Some(code) => info!("GUI process exited with status code: {code}"),
+                None => info!("GUI process terminated by signal"),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Goes back to the parent ring, or closes the GUI when already at the top level
+    fn cancel(&mut self) -> anyhow::Result<()> {
+        let Some(parent_idx) = self.menu_stack.pop() else {
+            info!("Menu cancelled");
+            return self.close_gui();
+        };
+
+        info!("Leaving actions menu");
+        self.scroll_offset = 0;
+        self.pointer_offset = (0.0, 0.0);
+        self.highlight_idx = Some(parent_idx);
+        self.send(&protocol::Command::PopMenu)
+    }
+
+    /// Replaces the ring with the actions of the highlighted entry
+    fn open_actions(&mut self, shortcut_files: &[DesktopFile]) -> anyhow::Result<()> {
+        let Some(idx) = self.highlight_idx else {
+            return Ok(());
+        };
+
+        let entry = &self.current_items(shortcut_files)[idx];
+        if entry.actions.is_empty() {
+            info!("Highlighted entry has no actions");
+            return Ok(());
+        }
+
+        self.menu_stack.push(idx);
+        self.scroll_offset = 0;
+        self.pointer_offset = (0.0, 0.0);
+        self.highlight_idx = Some(0);
+        self.send(&protocol::Command::PushMenu {
+            items: protocol_items(&entry.actions),
+        })
+    }
+
+    /// Spawns the GUI, checks that it speaks our protocol and sends it the top level entries
+    fn open_gui(&mut self, shortcut_files: &[DesktopFile]) -> anyhow::Result<()> {
+        info!("Looking for GUI exe at {:?}", self.gui_bin_path);
+
+        let mut child = Command::new(&self.gui_bin_path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .context("Failed to run GUI")?;
+
+        let mut stdin = child.stdin.take().unwrap();
+        let mut stdout = child.stdout.take().unwrap();
+
+        let handshake = write_message(
+            &mut stdin,
+            &protocol::Command::Hello {
+                version: PROTOCOL_VERSION,
+            },
+        )
+        .and_then(|()| read_reply(&mut stdout, HANDSHAKE_TIMEOUT));
+
+        match handshake {
+            Ok(Some(Reply::Ready { version })) if version == PROTOCOL_VERSION => {}
+            result => {
+                let _ = child.kill();
+                let _ = child.wait();
+                match result {
+                    Ok(Some(Reply::Ready { version })) => {
+                        bail!("GUI speaks protocol version {version}, expected {PROTOCOL_VERSION}")
+                    }
+                    Ok(Some(Reply::Error { message })) => bail!("GUI refused to start: {message}"),
+                    Ok(None) => bail!("GUI exited during the handshake"),
+                    Err(e) => return Err(e.context("GUI handshake failed")),
+                }
+            }
+        }
+
+        self.idle_duration = Some(Instant::now());
+        self.gui_stdin = Some(stdin);
+        self.gui_process = Some(child);
+        self.menu_stack.clear();
+
+        self.send(&protocol::Command::Theme {
+            theme: self.theme.clone(),
+        })?;
+        self.send(&protocol::Command::Items {
+            items: protocol_items(shortcut_files),
+        })
+    }
+
+    /// Writes `command` to the GUI, does nothing if it isn't running
+    fn send(&mut self, command: &protocol::Command) -> anyhow::Result<()> {
+        let Some(stdin) = self.gui_stdin.as_mut() else {
+            return Ok(());
+        };
+
+        write_message(stdin, command).context("Failed to write GUI stdin")
+    }
+
+    /// Adds pointer movement and highlights the segment it points towards once it leaves the
+    /// dead zone
+    fn flick(&mut self, dx: f64, dy: f64, segments: usize) {
+        let (x, y) = (self.pointer_offset.0 + dx, self.pointer_offset.1 + dy);
+        let distance = x.hypot(y);
+
+        if distance <= self.flick_dead_zone {
+            self.pointer_offset = (x, y);
+            return;
+        }
+
+        // Kept on the edge of the dead zone so turning around takes effect right away
+        let scale = self.flick_dead_zone / distance;
+        self.pointer_offset = (x * scale, y * scale);
+
+        // Only the angles are needed, they don't depend on the screen size
+        let geometry = RingGeometry::new(1.0, 1.0, segments, &self.theme);
+        if let Some(idx) = geometry.segment_towards(y.atan2(x).to_degrees() as f32) {
+            self.idle_duration = Some(Instant::now());
+            self.highlight_idx = Some(idx);
+        }
+    }
+
     pub fn handle_event(
         &mut self,
         event: EventType,
-        segments: usize,
         shortcut_files: &[DesktopFile],
     ) -> anyhow::Result<()> {
+        let segments = self.current_items(shortcut_files).len();
+
         match event {
-            EventType::MenuUp | EventType::MenuDown | EventType::Scroll(_)
-                if self.gui_process.is_none() =>
-            {
-                info!("Looking for GUI exe at {:?}", self.gui_bin_path);
+            EventType::ModifiersReleased => {
+                if self.launch_mode == LaunchMode::Release {
+                    self.launch_highlighted(shortcut_files)?;
+                }
+            }
 
-                let mut cmd = Command::new(&self.gui_bin_path);
-                cmd.arg(segments.to_string());
+            EventType::Cancel => {
+                if self.gui_process.is_some() {
+                    self.cancel()?;
+                }
+            }
 
-                for desktop_file in shortcut_files {
-                    if let Some(icon_path) = &desktop_file.icon {
-                        cmd.arg(icon_path);
-                    } else {
-                        cmd.arg("default");
-                    }
+            EventType::OpenActions => {
+                if self.gui_process.is_some() {
+                    self.idle_duration = Some(Instant::now());
+                    self.open_actions(shortcut_files)?;
+                }
+            }
+
+            EventType::Commit => {
+                self.launch_highlighted(shortcut_files)?;
+            }
+
+            // Handled by the main loop
+            EventType::Reload => {}
+
+            EventType::Quit => {
+                if self.gui_process.is_some() {
+                    self.close_gui()?;
                 }
+            }
 
-                let mut child = cmd
-                    .stdin(Stdio::piped())
-                    .spawn()
-                    .context("Failed to run GUI")?;
+            EventType::PointerMotion(dx, dy) => {
+                if self.gui_process.is_some() && self.flick_dead_zone > 0.0 {
+                    self.flick(dx, dy, segments);
+                }
+            }
 
+            EventType::Show => {
+                if self.gui_process.is_none() {
+                    self.open_gui(shortcut_files)?;
+                    self.highlight_idx = self.most_used(shortcut_files);
+                }
+            }
+
+            EventType::Select(idx) => {
+                if idx >= segments {
+                    warn!("Cannot select entry {idx}, the ring has {segments} entries");
+                    return Ok(());
+                }
+
+                if self.gui_process.is_none() {
+                    self.open_gui(shortcut_files)?;
+                }
                 self.idle_duration = Some(Instant::now());
-                self.gui_stdin = Some(child.stdin.take().unwrap());
-                self.gui_process = Some(child);
+                self.highlight_idx = Some(idx);
+            }
+
+            EventType::MenuUp | EventType::MenuDown | EventType::Scroll(_)
+                if self.gui_process.is_none() =>
+            {
+                self.open_gui(shortcut_files)?;
 
-                self.highlight_idx = Some(match event {
+                let first_idx = match event {
                     EventType::MenuUp => segments - 1,
                     EventType::MenuDown => 0,
-                    EventType::Scroll(d) if d < 0 => segments - 1,
-                    EventType::Scroll(d) if d > 0 => 0,
+                    // Every step counts, the first one lands on the first or last entry
+                    EventType::Scroll(d) => {
+                        self.scroll_offset = d;
+                        let start = if d > 0 { -1 } else { 0 };
+                        (start + d).rem_euclid(segments as i32) as usize
+                    }
                     _ => unreachable!(),
-                })
+                };
+                self.highlight_idx = Some(self.most_used(shortcut_files).unwrap_or(first_idx));
             }
 
             EventType::MenuUp => {
@@ -139,23 +468,169 @@ impl GuiState {
 
             EventType::Scroll(scroll_delta) => {
                 self.idle_duration = Some(Instant::now());
-                match scroll_delta.cmp(&0) {
-                    Ordering::Greater => {
-                        self.highlight_idx = Some(match self.highlight_idx {
-                            Some(val) => (val + 1) % segments,
-                            None => 0,
-                        })
-                    }
-                    Ordering::Less => {
-                        self.highlight_idx = Some(match self.highlight_idx {
-                            Some(val) => (val + segments - 1) % segments,
-                            None => segments - 1,
-                        });
-                    }
-                    Ordering::Equal => {}
+                let previous_offset = self.scroll_offset;
+                self.scroll_offset += scroll_delta;
+
+                // Several steps at once can skip past the start instead of landing on it
+                if self.cancel_on_scroll_back
+                    && previous_offset != 0
+                    && self.scroll_offset.signum() != previous_offset.signum()
+                {
+                    info!("Scrolled back to the start");
+                    return self.cancel();
                 }
+
+                // Without a highlight, scrolling forwards starts just before the first entry
+                let current = match (self.highlight_idx, scroll_delta.cmp(&0)) {
+                    (_, Ordering::Equal) => return Ok(()),
+                    (Some(val), _) => val as i32,
+                    (None, Ordering::Greater) => -1,
+                    (None, Ordering::Less) => 0,
+                };
+                self.highlight_idx =
+                    Some((current + scroll_delta).rem_euclid(segments as i32) as usize);
             }
         }
         Ok(())
     }
 }
+
+/// Reads one reply from the GUI, failing when it doesn't finish a line within `timeout`
+fn read_reply(stdout: &mut ChildStdout, timeout: Duration) -> anyhow::Result<Option<Reply>> {
+    let deadline = Instant::now() + timeout;
+    let mut line = Vec::new();
+
+    while !line.contains(&b'\n') {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let mut fds = [PollFd::new(stdout.as_fd(), PollFlags::POLLIN)];
+        let poll_timeout = PollTimeout::try_from(remaining).unwrap_or(PollTimeout::MAX);
+
+        if poll(&mut fds, poll_timeout).context("Failed to wait for the GUI")? == 0 {
+            bail!("GUI didn't answer within {}s", timeout.as_secs());
+        }
+
+        let mut chunk = [0; 512];
+        let read = stdout.read(&mut chunk).context("Failed to read message")?;
+        if read == 0 {
+            break;
+        }
+        line.extend_from_slice(&chunk[..read]);
+    }
+
+    read_message(&mut line.as_slice())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+    use std::sync::Mutex;
+    use std::{env, fs, process};
+
+    use super::*;
+    use crate::shortcut_parser::sort_shortcuts;
+
+    /// Held by tests that spawn processes, a script still open for writing in another test
+    /// while forking can't be executed
+    static SPAWN: Mutex<()> = Mutex::new(());
+
+    /// State whose GUI is a script that answers the handshake and then reads until stdin is
+    /// closed, along with the temporary dir holding it
+    fn gui_state(name: &str, config: &Config) -> (GuiState, PathBuf) {
+        let dir = env::temp_dir().join(format!("aeonium-{name}-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let gui_bin_path = dir.join("aeonium-gui");
+        let script = format!(
+            "#!/bin/sh\nread hello\necho '{{\"type\":\"ready\",\"version\":{PROTOCOL_VERSION}}}'\n\
+             exec cat >/dev/null\n"
+        );
+        fs::write(&gui_bin_path, script).unwrap();
+        fs::set_permissions(&gui_bin_path, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let proj_dirs = ProjectDirs::from("", "", "aeonium").unwrap();
+        let history = History::empty(dir.join("history"));
+        let mut gui_state = GuiState::new(&proj_dirs, config, history);
+        gui_state.gui_bin_path = gui_bin_path;
+        (gui_state, dir)
+    }
+
+    fn entries(count: usize) -> Vec<DesktopFile> {
+        (0..count)
+            .map(|idx| DesktopFile::stub(&format!("{idx}.desktop"), &idx.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn flicking_past_the_dead_zone_highlights_the_segment_in_that_direction() {
+        let (mut gui_state, dir) = gui_state("flick", &Config::default());
+        fs::remove_dir_all(&dir).unwrap();
+
+        // Four segments clockwise from the top, y points down
+        gui_state.flick(20.0, 20.0, 4);
+        assert_eq!(gui_state.highlight_idx, None);
+
+        gui_state.flick(20.0, 20.0, 4);
+        assert_eq!(gui_state.highlight_idx, Some(1));
+
+        // Kept on the edge of the dead zone, so turning around doesn't have to undo the way out
+        gui_state.flick(-60.0, -60.0, 4);
+        assert_eq!(gui_state.highlight_idx, Some(3));
+
+        gui_state.flick(100.0, -20.0, 4);
+        assert_eq!(gui_state.highlight_idx, Some(0));
+    }
+
+    #[test]
+    fn opening_with_several_scroll_steps_applies_all_of_them() {
+        let _spawn = SPAWN.lock().unwrap();
+        let (mut gui_state, dir) = gui_state("scroll-open", &Config::default());
+        let shortcut_files = entries(5);
+
+        let mut open_with = |steps| {
+            gui_state
+                .tick(Some(EventType::Scroll(steps)), &shortcut_files)
+                .unwrap();
+            let opened = (
+                gui_state.is_open(),
+                gui_state.highlight_idx,
+                gui_state.scroll_offset,
+            );
+            gui_state.close_gui().unwrap();
+            opened
+        };
+        let forwards = open_with(3);
+        let backwards = open_with(-2);
+        let single = open_with(1);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(forwards, (true, Some(2), 3));
+        assert_eq!(backwards, (true, Some(3), -2));
+        assert_eq!(single, (true, Some(0), 1));
+    }
+
+    #[test]
+    fn launches_move_entries_forward_when_sorting_by_frecency() {
+        let _spawn = SPAWN.lock().unwrap();
+        let mut config = Config::default();
+        config.shortcuts.sort = Sort::Frecency;
+        let (mut gui_state, dir) = gui_state("frecency", &config);
+        let mut shortcut_files = entries(3);
+
+        gui_state
+            .tick(Some(EventType::Select(2)), &shortcut_files)
+            .unwrap();
+        gui_state
+            .tick(Some(EventType::Commit), &shortcut_files)
+            .unwrap();
+        let recorded = gui_state.take_launch_recorded();
+        if recorded {
+            sort_shortcuts(&mut shortcut_files, &config.shortcuts, gui_state.history());
+        }
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(recorded);
+        assert!(!gui_state.take_launch_recorded());
+        assert!(!gui_state.is_open());
+        assert_eq!(shortcut_files[0].id, "2.desktop");
+    }
+}
@@ -0,0 +1,225 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use directories::ProjectDirs;
+use tracing::{info, warn};
+
+use crate::utils::write_atomic;
+
+/// Launches older than the newest `MAX_LAUNCHES` are dropped, from the file as well
+const MAX_LAUNCHES: usize = 1000;
+
+const HOUR: u64 = 60 * 60;
+const DAY: u64 = 24 * HOUR;
+
+#[derive(Debug)]
+struct Launch {
+    id: String,
+    timestamp: u64,
+}
+
+/// Launch history kept in the data dir, one `<unix timestamp> <desktop file ID>` line per launch
+#[derive(Debug)]
+pub struct History {
+    file_path: PathBuf,
+    launches: Vec<Launch>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+impl History {
+    pub fn load(proj_dirs: &ProjectDirs) -> anyhow::Result<Self> {
+        let file_path = proj_dirs.data_dir().join("history");
+
+        let contents = match fs::read_to_string(&file_path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                info!("No launch history at {}, starting fresh", file_path.display());
+                String::new()
+            }
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to read history {}", file_path.display()));
+            }
+        };
+
+        let launches: Vec<Launch> = contents
+            .lines()
+            .filter_map(|line| {
+                let parsed = line
+                    .split_once(' ')
+                    .and_then(|(timestamp, id)| Some((timestamp.parse().ok()?, id)));
+
+                if parsed.is_none() {
+                    warn!("Ignoring malformed history line `{line}`");
+                }
+
+                parsed.map(|(timestamp, id)| Launch {
+                    id: id.to_string(),
+                    timestamp,
+                })
+            })
+            .collect();
+
+        let mut history = History {
+            file_path,
+            launches,
+        };
+        if history.launches.len() > MAX_LAUNCHES {
+            history.compact()?;
+        }
+
+        Ok(history)
+    }
+
+    /// Drops the oldest launches beyond `MAX_LAUNCHES` and rewrites the file with the rest
+    fn compact(&mut self) -> anyhow::Result<()> {
+        let excess = self.launches.len().saturating_sub(MAX_LAUNCHES);
+        self.launches.drain(..excess);
+
+        let contents: String = self
+            .launches
+            .iter()
+            .map(|launch| format!("{} {}\n", launch.timestamp, launch.id))
+            .collect();
+        write_atomic(&self.file_path, &contents).context("Failed to compact launch history")
+    }
+
+    /// Appends a launch of `id` to the history file, compacting it once it grows too long
+    pub fn record(&mut self, id: &str) -> anyhow::Result<()> {
+        let launch = Launch {
+            id: id.to_string(),
+            timestamp: now(),
+        };
+
+        if let Some(parent) = self.file_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.file_path)
+            .with_context(|| format!("Failed to open history {}", self.file_path.display()))?;
+
+        writeln!(file, "{} {}", launch.timestamp, launch.id)
+            .context("Failed to write launch history")?;
+
+        self.launches.push(launch);
+        if self.launches.len() > MAX_LAUNCHES {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    /// Empty history recorded to `file_path`, for tests elsewhere in the crate
+    #[cfg(test)]
+    pub fn empty(file_path: PathBuf) -> Self {
+        History {
+            file_path,
+            launches: Vec::new(),
+        }
+    }
+
+    /// Sum of every launch of `id`, weighted by how recent it is
+    pub fn frecency(&self, id: &str) -> f64 {
+        self.frecency_at(id, now())
+    }
+
+    fn frecency_at(&self, id: &str, now: u64) -> f64 {
+        self.launches
+            .iter()
+            .filter(|launch| launch.id == id)
+            .map(|launch| weight(now.saturating_sub(launch.timestamp)))
+            // `sum` of nothing is -0.0, whose bits would sort above every score
+            .fold(0.0, |sum, weight| sum + weight)
+    }
+}
+
+/// Weight of a launch `age` seconds ago
+fn weight(age: u64) -> f64 {
+    match age {
+        age if age < 4 * HOUR => 8.0,
+        age if age < DAY => 4.0,
+        age if age < 7 * DAY => 2.0,
+        age if age < 30 * DAY => 1.0,
+        _ => 0.5,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(file_path: PathBuf, launches: &[(&str, u64)]) -> History {
+        History {
+            file_path,
+            launches: launches
+                .iter()
+                .map(|&(id, timestamp)| Launch {
+                    id: id.to_string(),
+                    timestamp,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn recent_launches_weigh_more() {
+        assert_eq!(weight(0), 8.0);
+        assert_eq!(weight(4 * HOUR - 1), 8.0);
+        assert_eq!(weight(4 * HOUR), 4.0);
+        assert_eq!(weight(DAY), 2.0);
+        assert_eq!(weight(7 * DAY), 1.0);
+        assert_eq!(weight(30 * DAY - 1), 1.0);
+        assert_eq!(weight(30 * DAY), 0.5);
+        assert_eq!(weight(u64::MAX), 0.5);
+    }
+
+    #[test]
+    fn frecency_sums_the_launches_of_an_entry() {
+        let now = 100 * DAY;
+        let history = history(
+            PathBuf::new(),
+            &[
+                ("firefox.desktop", now - HOUR),
+                ("firefox.desktop", now - 2 * DAY),
+                ("files.desktop", now - HOUR),
+                // Clocks can go backwards
+                ("firefox.desktop", now + HOUR),
+            ],
+        );
+
+        assert_eq!(history.frecency_at("firefox.desktop", now), 18.0);
+        assert_eq!(history.frecency_at("files.desktop", now), 8.0);
+        assert_eq!(history.frecency_at("mail.desktop", now), 0.0);
+        assert!(history.frecency_at("mail.desktop", now).is_sign_positive());
+    }
+
+    #[test]
+    fn compacting_keeps_the_newest_launches() {
+        let file_path =
+            std::env::temp_dir().join(format!("aeonium-history-{}", std::process::id()));
+        let launches: Vec<(&str, u64)> = (0..MAX_LAUNCHES as u64 + 5)
+            .map(|timestamp| ("files.desktop", timestamp))
+            .collect();
+        let mut history = history(file_path.clone(), &launches);
+
+        history.compact().unwrap();
+        let contents = fs::read_to_string(&file_path).unwrap();
+        fs::remove_file(&file_path).unwrap();
+
+        assert_eq!(history.launches.len(), MAX_LAUNCHES);
+        assert_eq!(history.launches[0].timestamp, 5);
+        assert_eq!(contents.lines().count(), MAX_LAUNCHES);
+        assert_eq!(contents.lines().next(), Some("5 files.desktop"));
+    }
+}
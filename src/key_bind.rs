@@ -0,0 +1,84 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{Context, bail};
+
+use crate::key_code::KeyMatch;
+
+/// A key combination such as `Ctrl+Shift+F10`, the last key triggers the bind
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyBind {
+    pub modifiers: Vec<KeyMatch>,
+    pub key: KeyMatch,
+}
+
+impl FromStr for KeyBind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut keys = parse_key_list(s)?;
+        let key = keys.pop().with_context(|| format!("Key bind `{s}` is empty"))?;
+
+        Ok(KeyBind {
+            modifiers: keys,
+            key,
+        })
+    }
+}
+
+impl fmt::Display for KeyBind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for modifier in &self.modifiers {
+            write!(f, "{modifier}+")?;
+        }
+        write!(f, "{}", self.key)
+    }
+}
+
+/// Parses a `+` separated list of key names, e.g. `Ctrl+Shift`
+pub fn parse_key_list(s: &str) -> anyhow::Result<Vec<KeyMatch>> {
+    if s.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    s.split('+')
+        .map(|name| {
+            if name.trim().is_empty() {
+                bail!("Empty key name in `{s}`");
+            }
+            name.parse()
+        })
+        .collect()
+}
+
+/// Validated bindings handed to the input checker thread
+#[derive(Debug, Clone)]
+pub struct KeyBinds {
+    pub scroll: Vec<KeyMatch>,
+    pub up: KeyBind,
+    pub down: KeyBind,
+    pub cancel: Option<KeyBind>,
+    pub actions: Option<KeyBind>,
+    pub commit: Option<KeyBind>,
+}
+
+impl KeyBinds {
+    /// Every key that has to be held for one of the binds
+    pub fn modifiers(&self) -> Vec<KeyMatch> {
+        let mut modifiers: Vec<KeyMatch> = Vec::new();
+        let optional = self.cancel.iter().chain(&self.actions).chain(&self.commit);
+        let all = self
+            .scroll
+            .iter()
+            .chain(&self.up.modifiers)
+            .chain(&self.down.modifiers)
+            .chain(optional.flat_map(|bind| &bind.modifiers));
+
+        for modifier in all {
+            if !modifiers.contains(modifier) {
+                modifiers.push(*modifier);
+            }
+        }
+        modifiers
+    }
+}
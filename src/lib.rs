@@ -0,0 +1,6 @@
+//! Code shared by `aeonium-menu` and `aeonium-gui`
+
+pub mod geometry;
+pub mod protocol;
+pub mod render;
+pub mod theme;
@@ -1,123 +1,23 @@
 use std::os::fd::{AsRawFd, BorrowedFd};
-use std::collections::HashMap;
 use std::fs::{File, OpenOptions};
 use std::os::unix::{fs::OpenOptionsExt, io::OwnedFd};
 use std::path::Path;
 use std::sync::mpsc::Sender;
+use std::sync::{Arc, RwLock};
 use std::collections::HashSet;
 
 use anyhow::{Context, anyhow};
 use input::event::keyboard::{KeyState, KeyboardEventTrait};
-use input::event::pointer::{Axis, PointerScrollEvent};
+use input::event::pointer::{Axis, ButtonState, PointerScrollEvent};
 use input::event::{Event, PointerEvent};
 use input::{Libinput, LibinputInterface};
 use libc::{O_ACCMODE, O_RDONLY, O_RDWR, O_WRONLY};
 use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
-use num_enum::TryFromPrimitive;
 
 use crate::EventType;
-
-#[allow(non_camel_case_types)]
-#[repr(u32)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, TryFromPrimitive)]
-pub enum KeyCode {
-    KEY_RESERVED = 0,
-    KEY_ESC = 1,
-    KEY_KEY1 = 2,
-    KEY_KEY2 = 3,
-    KEY_KEY3 = 4,
-    KEY_KEY4 = 5,
-    KEY_KEY5 = 6,
-    KEY_KEY6 = 7,
-    KEY_KEY7 = 8,
-    KEY_KEY8 = 9,
-    KEY_KEY9 = 10,
-    KEY_KEY0 = 11,
-    KEY_MINUS = 12,
-    KEY_EQUAL = 13,
-    KEY_BACKSPACE = 14,
-    KEY_TAB = 15,
-    KEY_Q = 16,
-    KEY_W = 17,
-    KEY_E = 18,
-    KEY_R = 19,
-    KEY_T = 20,
-    KEY_Y = 21,
-    KEY_U = 22,
-    KEY_I = 23,
-    KEY_O = 24,
-    KEY_P = 25,
-    KEY_LEFTBRACE = 26,
-    KEY_RIGHTBRACE = 27,
-    KEY_ENTER = 28,
-    KEY_LEFTCTRL = 29,
-    KEY_A = 30,
-    KEY_S = 31,
-    KEY_D = 32,
-    KEY_F = 33,
-    KEY_G = 34,
-    KEY_H = 35,
-    KEY_J = 36,
-    KEY_K = 37,
-    KEY_L = 38,
-    KEY_SEMICOLON = 39,
-    KEY_APOSTROPHE = 40,
-    KEY_GRAVE = 41,
-    KEY_LEFTSHIFT = 42,
-    KEY_BACKSLASH = 43,
-    KEY_Z = 44,
-    KEY_X = 45,
-    KEY_C = 46,
-    KEY_V = 47,
-    KEY_B = 48,
-    KEY_N = 49,
-    KEY_M = 50,
-    KEY_COMMA = 51,
-    KEY_DOT = 52,
-    KEY_SLASH = 53,
-    KEY_RIGHTSHIFT = 54,
-    KEY_KPASTERISK = 55,
-    KEY_LEFTALT = 56,
-    KEY_SPACE = 57,
-    KEY_CAPSLOCK = 58,
-    KEY_F1 = 59,
-    KEY_F2 = 60,
-    KEY_F3 = 61,
-    KEY_F4 = 62,
-    KEY_F5 = 63,
-    KEY_F6 = 64,
-    KEY_F7 = 65,
-    KEY_F8 = 66,
-    KEY_F9 = 67,
-    KEY_F10 = 68,
-    KEY_F13 = 183,
-    KEY_F14 = 184,
-    KEY_F15 = 185,
-    KEY_F16 = 186,
-    KEY_F17 = 187,
-    KEY_F18 = 188,
-    KEY_F19 = 189,
-    KEY_F20 = 190,
-    KEY_F21 = 191,
-    KEY_F22 = 192,
-    KEY_F23 = 193,
-    KEY_F24 = 194,
-    KEY_NUMLOCK = 69,
-    KEY_SCROLLLOCK = 70,
-    KEY_KP7 = 71,
-    KEY_KP8 = 72,
-    KEY_KP9 = 73,
-    KEY_KPMINUS = 74,
-    KEY_KP4 = 75,
-    KEY_KP5 = 76,
-    KEY_KP6 = 77,
-    KEY_KPPLUS = 78,
-    KEY_KP1 = 79,
-    KEY_KP2 = 80,
-    KEY_KP3 = 81,
-    KEY_KP0 = 82,
-    KEY_KPDOT = 83,
-}
+use crate::key_bind::{KeyBind, KeyBinds};
+use crate::key_code::{KeyCode, KeyMatch};
+use crate::scroll::ScrollSteps;
 
 pub struct Interface;
 
@@ -140,7 +40,14 @@ impl LibinputInterface for Interface {
 pub struct InputState {
     pressed_keys: HashSet<KeyCode>,
     just_pressed: HashSet<KeyCode>,
+    just_released: HashSet<KeyCode>,
+    /// Whole scroll steps since the last update
     wheel_delta: i32,
+    /// Scrolling that didn't add up to a whole step yet, kept across updates while the scroll
+    /// modifiers are held
+    scroll_steps: ScrollSteps,
+    /// Relative pointer movement since the last update
+    motion: (f64, f64),
 }
 
 impl Default for InputState {
@@ -154,44 +61,55 @@ impl InputState {
         Self {
             pressed_keys: HashSet::new(),
             just_pressed: HashSet::new(),
+            just_released: HashSet::new(),
             wheel_delta: 0,
+            scroll_steps: ScrollSteps::default(),
+            motion: (0.0, 0.0),
         }
     }
 
-    pub fn update(&mut self, input: &mut Libinput) {
+    /// Reads every pending event, `scroll_threshold` is the touchpad scroll distance per step
+    pub fn update(&mut self, input: &mut Libinput, scroll_threshold: f64) {
         input.dispatch().unwrap();
         self.wheel_delta = 0; // reset every cycle
+        self.motion = (0.0, 0.0);
         self.just_pressed.clear();
+        self.just_released.clear();
 
         for event in input {
             match event {
                 Event::Keyboard(k) => {
                     if let Ok(key) = KeyCode::try_from(k.key()) {
-                        match k.key_state() {
-                            KeyState::Pressed => {
-                                if !self.pressed_keys.contains(&key) {
-                                    self.just_pressed.insert(key);
-                                }
-                                self.pressed_keys.insert(key);
-                            }
-                            KeyState::Released => {
-                                self.pressed_keys.remove(&key);
-                            }
-                        }
+                        self.set_pressed(key, k.key_state() == KeyState::Pressed);
                     }
                 }
 
                 Event::Pointer(p) => match p {
-                    PointerEvent::ScrollWheel(p) => {
-                        let val = p.scroll_value(Axis::Vertical);
-                        self.wheel_delta = val.signum() as i32;
+                    // 120 per detent, hi-res wheels send fractions of that
+                    PointerEvent::ScrollWheel(p) if p.has_axis(Axis::Vertical) => {
+                        let steps = p.scroll_value_v120(Axis::Vertical) / 120.0;
+                        self.wheel_delta += self.scroll_steps.add_scroll(steps);
+                    }
+                    PointerEvent::ScrollFinger(p) if p.has_axis(Axis::Vertical) => {
+                        let value = p.scroll_value(Axis::Vertical);
+                        self.wheel_delta +=
+                            self.scroll_steps.add_smooth_scroll(value, scroll_threshold);
                     }
-                    PointerEvent::ScrollFinger(p) => {
-                        if p.has_axis(Axis::Vertical) {
-                            let val = p.scroll_value(Axis::Vertical);
-                            self.wheel_delta = val.signum() as i32;
+                    PointerEvent::ScrollContinuous(p) if p.has_axis(Axis::Vertical) => {
+                        let value = p.scroll_value(Axis::Vertical);
+                        self.wheel_delta +=
+                            self.scroll_steps.add_smooth_scroll(value, scroll_threshold);
+                    }
+                    // Buttons share the key codes, so they work in any bind
+                    PointerEvent::Button(p) => {
+                        if let Ok(key) = KeyCode::try_from(p.button()) {
+                            self.set_pressed(key, p.button_state() == ButtonState::Pressed);
                         }
                     }
+                    PointerEvent::Motion(p) => {
+                        self.motion.0 += p.dx();
+                        self.motion.1 += p.dy();
+                    }
                     _ => {}
                 },
 
@@ -200,23 +118,50 @@ impl InputState {
         }
     }
 
-    pub fn key_bind_pressed(&self, modifiers: &HashSet<KeyCode>, main: KeyCode) -> bool {
-        modifiers.iter().all(|m| self.pressed_keys.contains(m)) && self.just_pressed.contains(&main)
+    fn set_pressed(&mut self, key: KeyCode, pressed: bool) {
+        if pressed {
+            if !self.pressed_keys.contains(&key) {
+                self.just_pressed.insert(key);
+            }
+            self.pressed_keys.insert(key);
+        } else if self.pressed_keys.remove(&key) {
+            self.just_released.insert(key);
+        }
+    }
+
+    pub fn key_bind_pressed(&self, key_bind: &KeyBind) -> bool {
+        key_bind.modifiers.iter().all(|m| m.any_in(&self.pressed_keys))
+            && key_bind.key.any_in(&self.just_pressed)
+    }
+
+    pub fn any_released(&self, modifiers: &[KeyMatch]) -> bool {
+        modifiers.iter().any(|m| m.any_in(&self.just_released))
+    }
+
+    /// Pointer movement while `modifiers` are held, if there was any. Without modifiers every
+    /// movement on the system would be sent, so none is
+    pub fn moved(&self, modifiers: &[KeyMatch]) -> Option<(f64, f64)> {
+        let held = !modifiers.is_empty() && modifiers.iter().all(|k| k.any_in(&self.pressed_keys));
+        (held && self.motion != (0.0, 0.0)).then_some(self.motion)
     }
 
-    pub fn scrolled(&self, modifiers: &HashSet<KeyCode>) -> i32 {
-        if modifiers.iter().all(|k| self.pressed_keys.contains(k)) {
+    /// Whole scroll steps while `modifiers` are held, scrolling without them doesn't add up
+    pub fn scrolled(&mut self, modifiers: &[KeyMatch]) -> i32 {
+        if modifiers.iter().all(|k| k.any_in(&self.pressed_keys)) {
             self.wheel_delta
         } else {
+            self.scroll_steps.reset();
             0
         }
     }
 }
 
+/// Sends the events for key binds and scrolling, `key_binds` and `scroll_threshold` are read
+/// again for every batch of input so reloads take effect
 pub fn run_input_checker(
     tx: Sender<EventType>,
-    modifiers: &HashSet<KeyCode>,
-    menu_control_keys: HashMap<&str, KeyCode>,
+    key_binds: Arc<RwLock<KeyBinds>>,
+    scroll_threshold: Arc<RwLock<f64>>,
 ) -> anyhow::Result<()> {
     let mut libinput = Libinput::new_with_udev(Interface);
 
@@ -236,22 +181,63 @@ pub fn run_input_checker(
         // Block until fd is ready
         poll(&mut fds, PollTimeout::NONE)?;
 
-        state.update(&mut libinput);
+        // Re-read every time, the binds are swapped out when the config is reloaded
+        let key_binds = key_binds
+            .read()
+            .map_err(|_| anyhow!("Key binds lock poisoned"))?;
+
+        let scroll_threshold = *scroll_threshold
+            .read()
+            .map_err(|_| anyhow!("Scroll threshold lock poisoned"))?;
 
-        if state.key_bind_pressed(modifiers, menu_control_keys["up"]) {
+        state.update(&mut libinput, scroll_threshold);
+        let modifiers = key_binds.modifiers();
+
+        if state.key_bind_pressed(&key_binds.up) {
             tx.send(EventType::MenuUp)
                 .context("Failed to send MenuUp event")?;
         }
 
-        if state.key_bind_pressed(modifiers, menu_control_keys["down"]) {
+        if state.key_bind_pressed(&key_binds.down) {
             tx.send(EventType::MenuDown)
                 .context("Failed to send MenuDown event")?;
         }
 
-        let delta = state.scrolled(modifiers);
+        if let Some(cancel) = &key_binds.cancel
+            && state.key_bind_pressed(cancel)
+        {
+            tx.send(EventType::Cancel)
+                .context("Failed to send Cancel event")?;
+        }
+
+        if let Some(actions) = &key_binds.actions
+            && state.key_bind_pressed(actions)
+        {
+            tx.send(EventType::OpenActions)
+                .context("Failed to send OpenActions event")?;
+        }
+
+        if let Some(commit) = &key_binds.commit
+            && state.key_bind_pressed(commit)
+        {
+            tx.send(EventType::Commit)
+                .context("Failed to send Commit event")?;
+        }
+
+        let delta = state.scrolled(&key_binds.scroll);
         if delta != 0 {
             tx.send(EventType::Scroll(delta))
                 .context(format!("Failed to send Scroll event with delta {}", delta))?;
         }
+
+        if let Some((dx, dy)) = state.moved(&key_binds.scroll) {
+            tx.send(EventType::PointerMotion(dx, dy))
+                .context("Failed to send PointerMotion event")?;
+        }
+
+        if state.any_released(&modifiers) {
+            tx.send(EventType::ModifiersReleased)
+                .context("Failed to send ModifiersReleased event")?;
+        }
     }
 }
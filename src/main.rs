@@ -1,62 +1,311 @@
-use std::collections::{HashMap, HashSet};
+use std::fs::{self, OpenOptions};
 use std::result::Result::Ok;
-use std::sync::mpsc::{self, RecvTimeoutError};
-use std::thread;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, RwLock};
 use std::time::Duration;
+use std::{env, thread};
 
+use anyhow::{Context, bail};
 use directories::ProjectDirs;
 use gui_state::GuiState;
-use tracing::error;
+use tracing::{error, info, warn};
 
 use crate::gui_state::EventType;
-use crate::libinput_events::KeyCode;
-use crate::shortcut_parser::get_shortcuts;
-use crate::config::Config;
+use crate::shortcut_parser::{get_shortcuts, sort_shortcuts};
+use crate::config::{Config, InputBackend, LaunchMode};
+use crate::history::History;
+use crate::shortcut_parser::DesktopFile;
+use crate::key_bind::KeyBinds;
 
+mod check;
+mod control_socket;
 mod gui_state;
+mod history;
+// Binds are still validated without libinput, they just aren't read
+#[cfg_attr(not(feature = "libinput"), allow(dead_code))]
+mod key_bind;
+#[cfg_attr(not(feature = "libinput"), allow(dead_code))]
+mod key_code;
+#[cfg(feature = "libinput")]
 mod libinput_events;
+#[cfg_attr(not(feature = "libinput"), allow(dead_code))]
+mod scroll;
 mod shortcut_parser;
 mod utils;
+mod watcher;
 mod config;
 
-fn main() {
+const USAGE: &str = "\
+Usage: aeonium-menu [--daemon | --foreground] [--log-level <level>]
+       aeonium-menu <command>
+
+Options:
+  --daemon              Detach from the terminal and log to aeonium.log in the data dir
+  --foreground          Stay attached to the terminal and log to stdout [default]
+  --log-level <level>   One of error, warn, info, debug or trace [default: debug]
+
+Commands, forwarded to the running instance:
+  show, next, prev, select <n>, launch, cancel, reload, status
+
+  Without libinput, `launch_mode = \"release\"` never sees modifiers being released,
+  so only `launch` starts the highlighted entry
+
+  check-config          Report problems in the config and shortcuts, then exit";
+
+struct Args {
+    daemon: bool,
+    log_level: tracing::Level,
+    check_config: bool,
+    /// Client command for the running instance, e.g. `select 2`
+    command: Option<String>,
+}
+
+fn parse_args() -> anyhow::Result<Args> {
+    let mut args = Args {
+        daemon: false,
+        log_level: tracing::Level::DEBUG,
+        check_config: false,
+        command: None,
+    };
+    let mut command = Vec::new();
+
+    let mut argv = env::args().skip(1);
+    while let Some(arg) = argv.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                std::process::exit(0);
+            }
+            "--daemon" => args.daemon = true,
+            "--foreground" => args.daemon = false,
+            "--log-level" => {
+                let level = argv.next().context("`--log-level` expects a level")?;
+                args.log_level = level
+                    .parse()
+                    .with_context(|| format!("Invalid log level `{level}`"))?;
+            }
+            "check-config" if command.is_empty() => args.check_config = true,
+            flag if flag.starts_with('-') => bail!("Unknown option `{flag}`"),
+            _ => command.push(arg),
+        }
+    }
+
+    if !command.is_empty() {
+        let command = command.join(" ");
+        // Catch typos before looking for a running instance
+        if !command.trim().eq_ignore_ascii_case("status") {
+            control_socket::parse_command(&command)?;
+        }
+        args.command = Some(command);
+    }
+
+    Ok(args)
+}
+
+/// Forwards `command` to the running instance and prints its reply
+fn run_client(command: &str) -> ! {
+    match control_socket::send_command(command) {
+        Ok(reply) => {
+            if !reply.is_empty() {
+                println!("{reply}");
+            }
+            std::process::exit(0);
+        }
+        Err(e) => {
+            eprintln!("Error: {e:#}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Switches logging to `aeonium.log` in the data dir and detaches from the terminal
+fn daemonize(args: &Args, proj_dirs: &ProjectDirs) -> anyhow::Result<()> {
+    fs::create_dir_all(proj_dirs.data_dir())?;
+    let log_path = proj_dirs.data_dir().join("aeonium.log");
+    let log_file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_path)
+        .with_context(|| format!("Failed to open log file {}", log_path.display()))?;
+
+    println!("Running in the background, logging to {}", log_path.display());
     tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::DEBUG)
+        .with_max_level(args.log_level)
+        .with_ansi(false)
+        .with_writer(Mutex::new(log_file))
         .init();
 
+    // Forking has to happen before any thread is spawned
+    // Safety: no other threads exist yet
+    if unsafe { libc::daemon(0, 0) } != 0 {
+        bail!("Failed to detach: {}", std::io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Turns SIGINT and SIGTERM into a `Quit` event, so the main loop can clean up on the way out.
+/// Has to be called before any other thread is spawned, they inherit the blocked signals
+fn forward_signals(tx: Sender<EventType>) -> anyhow::Result<()> {
+    // Safety: the set is initialised by sigemptyset before it is used
+    let set = unsafe {
+        let mut set: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut set);
+        libc::sigaddset(&mut set, libc::SIGINT);
+        libc::sigaddset(&mut set, libc::SIGTERM);
+        set
+    };
+
+    // Safety: `set` is a valid signal set, the old mask isn't needed
+    let ret = unsafe { libc::pthread_sigmask(libc::SIG_BLOCK, &set, std::ptr::null_mut()) };
+    if ret != 0 {
+        bail!(
+            "Failed to block signals: {}",
+            std::io::Error::from_raw_os_error(ret)
+        );
+    }
+
+    thread::spawn(move || {
+        let mut signal = 0;
+        // Safety: both pointers are valid for the whole call
+        if unsafe { libc::sigwait(&set, &mut signal) } == 0 {
+            info!("Received signal {signal}, shutting down");
+            let _ = tx.send(EventType::Quit);
+        }
+    });
+
+    Ok(())
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|e| {
+        eprintln!("Error: {e:#}\n\n{USAGE}");
+        std::process::exit(2);
+    });
+
+    if let Some(command) = &args.command {
+        run_client(command);
+    }
+
     let proj_dirs = ProjectDirs::from("", "", "aeonium").unwrap_or_else(|| {
-            error!("Fatal error: Home directory not found");
+            eprintln!("Fatal error: Home directory not found");
             std::process::exit(1);
     });
 
-    let config_vals = Config::parse(&proj_dirs).unwrap_or_else(|e| {
+    if args.check_config {
+        std::process::exit(if check::run_check(&proj_dirs) { 0 } else { 1 });
+    }
+
+    // Logs go to the terminal until detaching, so problems with the config are still seen
+    let subscriber = tracing_subscriber::fmt().with_max_level(args.log_level);
+    let startup_logging = if args.daemon {
+        Some(tracing::subscriber::set_default(subscriber.finish()))
+    } else {
+        subscriber.init();
+        None
+    };
+
+    // Held for the lifetime of the process
+    let _instance_lock = control_socket::lock_instance().unwrap_or_else(|e| {
             error!("Fatal error: {:?}", e);
             std::process::exit(1);
     });
 
-    let modifiers: HashSet<KeyCode> = vec![KeyCode::KEY_LEFTCTRL, KeyCode::KEY_LEFTSHIFT]
-        .into_iter()
-        .collect();
+    let mut config_vals = Config::parse(&proj_dirs).unwrap_or_else(|e| {
+            error!("Fatal error: {:?}", e);
+            std::process::exit(1);
+    });
 
-    let menu_control_keys: HashMap<&str, KeyCode> =
-        HashMap::from([("up", KeyCode::KEY_F10), ("down", KeyCode::KEY_F9)]);
+    let key_binds = config_vals.bindings.parse().unwrap_or_else(|e| {
+            error!("Fatal error: {:?}", e);
+            std::process::exit(1);
+    });
 
-    let shortcut_files = get_shortcuts(&proj_dirs).unwrap_or_else(|e| {
-            error!("Fatal Error: {:?}", e);
+    let history = History::load(&proj_dirs).unwrap_or_else(|e| {
+            error!("Fatal error: {:?}", e);
             std::process::exit(1);
     });
-    let segments = shortcut_files.len();
 
+    let mut shortcut_files =
+        get_shortcuts(&proj_dirs, &config_vals.shortcuts, &history).unwrap_or_else(|e| {
+            error!("Fatal Error: {:?}", e);
+            std::process::exit(1);
+        });
+
+    drop(startup_logging);
+    if args.daemon {
+        daemonize(&args, &proj_dirs).unwrap_or_else(|e| {
+            eprintln!("Fatal error: {:?}", e);
+            std::process::exit(1);
+        });
+    }
+
+    let key_binds = Arc::new(RwLock::new(key_binds));
+    let scroll_threshold = Arc::new(RwLock::new(config_vals.scroll_threshold));
     let (tx, rx) = mpsc::channel();
 
-    thread::spawn(move || {
-        if let Err(e) = libinput_events::run_input_checker(tx, &modifiers, menu_control_keys) {
-            error!("Fatal Error: {:?}", e);
+    if let Err(e) = forward_signals(tx.clone()) {
+        warn!("Signals aren't handled, the socket stays behind on exit: {e:?}");
+    }
+
+    let libinput_enabled = match config_vals.input_backend {
+        InputBackend::Libinput if cfg!(feature = "libinput") => true,
+        InputBackend::Libinput => {
+            warn!("Built without libinput, only the control socket can drive the menu");
+            false
+        }
+        InputBackend::None => {
+            info!("libinput disabled, only the control socket can drive the menu");
+            false
+        }
+    };
+
+    if !libinput_enabled && config_vals.launch_mode == LaunchMode::Release {
+        warn!("No modifiers are read in release launch mode, only `launch` starts an entry");
+    }
+
+    // Removes the socket file once main returns
+    let _socket_file = match control_socket::bind_control_socket() {
+        Ok((listener, socket_file)) => {
+            let socket_tx = tx.clone();
+            thread::spawn(move || control_socket::run_control_socket(listener, socket_tx));
+            Some(socket_file)
+        }
+        // Without libinput nothing else can open the menu
+        Err(e) if !libinput_enabled => {
+            error!("Fatal error: {:?}", e);
             std::process::exit(1);
+        }
+        Err(e) => {
+            warn!("Control socket unavailable: {:?}", e);
+            None
+        }
+    };
+
+    let watcher_tx = tx.clone();
+    let watcher_dirs = proj_dirs.clone();
+    thread::spawn(move || {
+        if let Err(e) = watcher::run_watcher(watcher_tx, watcher_dirs) {
+            warn!("Hot reload unavailable: {:?}", e);
         };
     });
 
-    let mut gui_state = GuiState::new(&proj_dirs);
+    #[cfg(feature = "libinput")]
+    if libinput_enabled {
+        let input_key_binds = Arc::clone(&key_binds);
+        let input_scroll_threshold = Arc::clone(&scroll_threshold);
+        thread::spawn(move || {
+            if let Err(e) =
+                libinput_events::run_input_checker(tx, input_key_binds, input_scroll_threshold)
+            {
+                error!("Fatal Error: {:?}", e);
+                std::process::exit(1);
+            };
+        });
+    }
+
+    let mut gui_state = GuiState::new(&proj_dirs, &config_vals, history);
+    let mut pending_reload = false;
 
     loop {
         let event = match rx.recv_timeout(Duration::from_millis(config_vals.timeout)) {
@@ -68,9 +317,52 @@ fn main() {
             }
         };
 
-        if let Err(e) = gui_state.tick(event, segments, &shortcut_files) {
+        if let Some(EventType::Reload) = event {
+            pending_reload = true;
+        }
+        let quit = matches!(event, Some(EventType::Quit));
+
+        if let Err(e) = gui_state.tick(event, &shortcut_files) {
             error!("Fatal error: {:?}", e);
             std::process::exit(1);
         };
+
+        if quit {
+            break;
+        }
+
+        // Launching closes the ring, so the entries can move before it shows them again
+        if gui_state.take_launch_recorded() {
+            sort_shortcuts(&mut shortcut_files, &config_vals.shortcuts, gui_state.history());
+        }
+
+        // Reloading swaps out the entries, so wait until the ring isn't showing them
+        if pending_reload && !gui_state.is_open() {
+            pending_reload = false;
+
+            match reload(&proj_dirs, &gui_state) {
+                Ok((new_config, new_key_binds, new_shortcut_files)) => {
+                    gui_state.apply_config(&new_config);
+                    *key_binds.write().unwrap() = new_key_binds;
+                    *scroll_threshold.write().unwrap() = new_config.scroll_threshold;
+                    config_vals = new_config;
+                    shortcut_files = new_shortcut_files;
+                    info!("Reloaded config and shortcuts");
+                }
+                Err(e) => warn!("Reload failed, keeping the previous config: {:?}", e),
+            }
+        }
     }
 }
+
+/// Loads the config, key binds and shortcuts again, nothing is applied unless all of them succeed
+fn reload(
+    proj_dirs: &ProjectDirs,
+    gui_state: &GuiState,
+) -> anyhow::Result<(Config, KeyBinds, Vec<DesktopFile>)> {
+    let config_vals = Config::parse(proj_dirs)?;
+    let key_binds = config_vals.bindings.parse()?;
+    let shortcut_files = get_shortcuts(proj_dirs, &config_vals.shortcuts, gui_state.history())?;
+
+    Ok((config_vals, key_binds, shortcut_files))
+}
@@ -0,0 +1,203 @@
+//! Messages between `aeonium-menu` and `aeonium-gui`.
+//!
+//! The menu writes [`Command`]s to the GUI's stdin and the GUI answers with [`Reply`]s on its
+//! stdout, one JSON object per line. The first command is always [`Command::Hello`], the GUI
+//! answers it with [`Reply::Ready`] when it speaks the same [`PROTOCOL_VERSION`], otherwise
+//! with [`Reply::Error`] before exiting.
+
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+use anyhow::Context;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::theme::{Rgba, Theme};
+
+/// Bumped on every incompatible change to the messages below
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// An entry on the ring
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Item {
+    pub name: String,
+    /// Image file to show, the built in default icon when `None`
+    #[serde(default)]
+    pub icon: Option<PathBuf>,
+    /// Overrides `theme.segment_color` for this entry
+    #[serde(default)]
+    pub color: Option<Rgba>,
+    /// Short texts drawn next to the icon, e.g. a count
+    #[serde(default)]
+    pub badges: Vec<String>,
+}
+
+/// Sent from the menu to the GUI
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Command {
+    Hello {
+        version: u32,
+    },
+    Theme {
+        theme: Theme,
+    },
+    /// Replaces every ring with a new top level one
+    Items {
+        items: Vec<Item>,
+    },
+    /// Shows a nested ring, e.g. the desktop actions of an entry
+    PushMenu {
+        items: Vec<Item>,
+    },
+    /// Goes back to the ring below the current one
+    PopMenu,
+    Highlight {
+        index: usize,
+    },
+    Close,
+}
+
+/// Sent from the GUI to the menu
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Reply {
+    Ready { version: u32 },
+    Error { message: String },
+}
+
+/// Writes `message` as a single line and flushes it
+pub fn write_message<T: Serialize>(writer: &mut impl Write, message: &T) -> anyhow::Result<()> {
+    let mut line = serde_json::to_string(message).context("Failed to encode message")?;
+    line.push('\n');
+
+    writer
+        .write_all(line.as_bytes())
+        .context("Failed to write message")?;
+    writer.flush().context("Failed to flush message")
+}
+
+/// Reads the next message, `None` once the other side closed the stream
+pub fn read_message<T: DeserializeOwned>(reader: &mut impl BufRead) -> anyhow::Result<Option<T>> {
+    let mut line = String::new();
+    if reader
+        .read_line(&mut line)
+        .context("Failed to read message")?
+        == 0
+    {
+        return Ok(None);
+    }
+
+    serde_json::from_str(&line)
+        .with_context(|| format!("Invalid message `{}`", line.trim()))
+        .map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn round_trip<T>(messages: &[T])
+    where
+        T: Serialize + DeserializeOwned + PartialEq + std::fmt::Debug,
+    {
+        let mut buffer = Vec::new();
+        for message in messages {
+            write_message(&mut buffer, message).unwrap();
+        }
+
+        let mut reader = Cursor::new(buffer);
+        for message in messages {
+            assert_eq!(
+                read_message::<T>(&mut reader).unwrap().as_ref(),
+                Some(message)
+            );
+        }
+        assert_eq!(read_message::<T>(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn commands_round_trip() {
+        let items = vec![
+            Item {
+                name: "Firefox\nWeb Browser".to_string(),
+                icon: Some(PathBuf::from("/usr/share/icons/firefox.png")),
+                color: Some(Rgba::new(255, 0, 0, 128)),
+                badges: vec!["3".to_string()],
+            },
+            Item {
+                name: "Files".to_string(),
+                icon: None,
+                color: None,
+                badges: Vec::new(),
+            },
+        ];
+
+        round_trip(&[
+            Command::Hello {
+                version: PROTOCOL_VERSION,
+            },
+            Command::Theme {
+                theme: Theme::default(),
+            },
+            Command::Items {
+                items: items.clone(),
+            },
+            Command::PushMenu { items },
+            Command::Highlight { index: 1 },
+            Command::PopMenu,
+            Command::Close,
+        ]);
+    }
+
+    #[test]
+    fn replies_round_trip() {
+        round_trip(&[
+            Reply::Ready {
+                version: PROTOCOL_VERSION,
+            },
+            Reply::Error {
+                message: "Unsupported version".to_string(),
+            },
+        ]);
+    }
+
+    #[test]
+    fn encoding_is_stable() {
+        let mut buffer = Vec::new();
+        write_message(&mut buffer, &Command::Highlight { index: 2 }).unwrap();
+        write_message(&mut buffer, &Command::PopMenu).unwrap();
+
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "{\"type\":\"highlight\",\"index\":2}\n{\"type\":\"pop_menu\"}\n"
+        );
+    }
+
+    #[test]
+    fn optional_item_fields_can_be_left_out() {
+        let command: Command =
+            serde_json::from_str(r#"{"type":"items","items":[{"name":"Files"}]}"#).unwrap();
+
+        assert_eq!(
+            command,
+            Command::Items {
+                items: vec![Item {
+                    name: "Files".to_string(),
+                    icon: None,
+                    color: None,
+                    badges: Vec::new(),
+                }]
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_messages_are_rejected() {
+        let mut reader = Cursor::new("{\"type\":\"explode\"}\n");
+        assert!(read_message::<Command>(&mut reader).is_err());
+    }
+}
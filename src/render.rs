@@ -0,0 +1,352 @@
+//! Software rendering of the ring, for previews and tests without a display.
+//!
+//! The ring is described as an SVG document laid out like `aeonium-gui` draws it and then
+//! rasterised with resvg, so no window or GPU is needed.
+
+use std::fmt::Write;
+use std::fs;
+use std::sync::Arc;
+
+use anyhow::{Context, ensure};
+use resvg::tiny_skia::Pixmap;
+use resvg::usvg::{self, ImageHrefResolver, ImageKind, Transform, fontdb};
+use tracing::warn;
+
+use crate::geometry::{Rect, RingGeometry, Segment};
+use crate::protocol::Item;
+use crate::theme::{Rgba, Theme};
+
+/// Shown for entries without an icon
+pub static DEFAULT_ICON: &[u8] = include_bytes!("../assets/default.png");
+
+/// Image href standing in for [`DEFAULT_ICON`]
+const DEFAULT_ICON_HREF: &str = "aeonium:default-icon";
+
+/// Renders the ring with `items` onto a `width` x `height` image
+pub fn render(
+    width: u32,
+    height: u32,
+    items: &[Item],
+    highlight: Option<usize>,
+    theme: &Theme,
+) -> anyhow::Result<Pixmap> {
+    if let Some(idx) = highlight {
+        ensure!(
+            idx < items.len(),
+            "Highlight index {idx} out of bounds for {} entries",
+            items.len()
+        );
+    }
+
+    let scene = Scene::new(theme);
+    let svg = scene.svg(width as f32, height as f32, items, highlight);
+
+    let tree = usvg::Tree::from_str(&svg, &scene.options).context("Failed to lay out the ring")?;
+    let mut pixmap = Pixmap::new(width, height).context("Image size must not be zero")?;
+    resvg::render(&tree, Transform::default(), &mut pixmap.as_mut());
+
+    Ok(pixmap)
+}
+
+struct Scene<'a> {
+    options: usvg::Options<'static>,
+    /// Family to write text in, no text is drawn without any font
+    font_family: Option<String>,
+    theme: &'a Theme,
+}
+
+impl<'a> Scene<'a> {
+    fn new(theme: &'a Theme) -> Self {
+        let mut options = usvg::Options::default();
+
+        let default_resolver = ImageHrefResolver::default_string_resolver();
+        options.image_href_resolver.resolve_string = Box::new(move |href, opts| {
+            if href == DEFAULT_ICON_HREF {
+                Some(ImageKind::PNG(Arc::new(DEFAULT_ICON.to_vec())))
+            } else {
+                default_resolver(href, opts)
+            }
+        });
+
+        let font_family = load_fonts(options.fontdb_mut(), theme);
+
+        Scene {
+            options,
+            font_family,
+            theme,
+        }
+    }
+
+    fn svg(&self, width: f32, height: f32, items: &[Item], highlight: Option<usize>) -> String {
+        let theme = self.theme;
+        let mut svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}">"#
+        );
+        svg += &format!(
+            r#"<rect width="{width}" height="{height}" {}/>"#,
+            fill(theme.background)
+        );
+
+        let geometry = RingGeometry::new(width, height, items.len(), theme);
+
+        for (idx, item) in items.iter().enumerate() {
+            let highlighted = highlight == Some(idx);
+
+            if highlighted && theme.outline_width > 0.0 {
+                let path = ring_segment(&geometry, geometry.outline(idx, theme.outline_width));
+                svg += &format!(r#"<path d="{path}" {}/>"#, fill(theme.outline_color));
+            }
+
+            let segment_color = if highlighted {
+                theme.highlight_color
+            } else {
+                item.color.unwrap_or(theme.segment_color)
+            };
+            let path = ring_segment(&geometry, geometry.segment(idx));
+            svg += &format!(r#"<path d="{path}" {}/>"#, fill(segment_color));
+
+            // A square, resvg fits the image into it keeping its aspect ratio
+            let icon = geometry.icon_rect(idx, 1.0, 1.0);
+            let href = match &item.icon {
+                Some(path) => escape(&path.to_string_lossy()),
+                None => DEFAULT_ICON_HREF.to_string(),
+            };
+            svg += &format!(
+                r#"<image href="{href}" x="{}" y="{}" width="{}" height="{}" preserveAspectRatio="xMidYMid meet"/>"#,
+                icon.x, icon.y, icon.width, icon.height
+            );
+
+            if !item.badges.is_empty() {
+                self.badges(&mut svg, &item.badges, icon);
+            }
+
+            if theme.segment_labels {
+                let size = theme.label_font_size;
+                let text = self.fit_text(&item.name, size, geometry.outer_radius);
+                let rect = geometry.label_rect(idx, self.text_width(&text, size), size);
+                let (x, y) = (rect.x + rect.width / 2.0, rect.y + rect.height / 2.0);
+                self.text(&mut svg, &text, x, y, size, "middle");
+            }
+
+            if highlighted {
+                let size = theme.font_size;
+                let text = self.fit_text(&item.name, size, geometry.inner_radius * 1.8);
+                let center = geometry.center;
+                self.text(&mut svg, &text, center.x, center.y, size, "middle");
+            }
+        }
+
+        svg += "</svg>";
+        svg
+    }
+
+    /// Stacks `badges` downwards from the top right corner of an icon
+    fn badges(&self, svg: &mut String, badges: &[String], icon: Rect) {
+        let size = self.theme.label_font_size;
+        let padding = size / 4.0;
+        let mut y = icon.y;
+
+        for badge in badges {
+            let text = self.fit_text(badge, size, icon.width);
+            let width = self.text_width(&text, size) + padding * 2.0;
+            let x = icon.x + icon.width - width / 2.0;
+
+            let _ = write!(
+                svg,
+                r#"<rect x="{x}" y="{}" width="{width}" height="{size}" rx="{}" {}/>"#,
+                y - size / 2.0,
+                size / 4.0,
+                fill(self.theme.highlight_color)
+            );
+            self.text(svg, &text, x + padding, y, size, "start");
+
+            y += size + padding;
+        }
+    }
+
+    /// Writes `text` vertically centered on `y`
+    fn text(&self, svg: &mut String, text: &str, x: f32, y: f32, size: f32, anchor: &str) {
+        let Some(family) = &self.font_family else {
+            return;
+        };
+
+        let _ = write!(
+            svg,
+            r#"<text x="{x}" y="{y}" font-family="{}" font-size="{size}" letter-spacing="{}" text-anchor="{anchor}" dominant-baseline="central" {}>{}</text>"#,
+            escape(family),
+            spacing(size),
+            fill(self.theme.text_color),
+            escape(text)
+        );
+    }
+
+    /// Width of `text` once laid out, 0 without any font
+    fn text_width(&self, text: &str, size: f32) -> f32 {
+        let mut svg =
+            String::from(r#"<svg xmlns="http://www.w3.org/2000/svg" width="1" height="1">"#);
+        self.text(&mut svg, text, 0.0, 0.0, size, "start");
+        svg += "</svg>";
+
+        usvg::Tree::from_str(&svg, &self.options)
+            .map(|tree| tree.root().abs_bounding_box().width())
+            .unwrap_or(0.0)
+    }
+
+    /// Shortens `text` with `...` until it is at most `max_width` wide
+    fn fit_text(&self, text: &str, size: f32, max_width: f32) -> String {
+        if self.text_width(text, size) <= max_width {
+            return text.to_string();
+        }
+
+        let mut chars: Vec<char> = text.chars().collect();
+        while !chars.is_empty() {
+            chars.pop();
+            let shortened = format!("{}...", chars.iter().collect::<String>().trim_end());
+            if self.text_width(&shortened, size) <= max_width {
+                return shortened;
+            }
+        }
+
+        String::new()
+    }
+}
+
+/// Loads the theme's font, or the system fonts without one, and returns the family to use
+fn load_fonts(db: &mut fontdb::Database, theme: &Theme) -> Option<String> {
+    if let Some(path) = &theme.font {
+        match fs::read(path) {
+            Ok(data) => {
+                let ids = db.load_font_source(fontdb::Source::Binary(Arc::new(data)));
+                if let Some(face) = ids.first().and_then(|&id| db.face(id))
+                    && let Some((family, _)) = face.families.first()
+                {
+                    return Some(family.clone());
+                }
+                warn!("No usable font in {}", path.display());
+            }
+            Err(e) => warn!("Failed to read font {}: {e}", path.display()),
+        }
+    }
+
+    db.load_system_fonts();
+
+    let sans_serif = fontdb::Query {
+        families: &[fontdb::Family::SansSerif],
+        ..Default::default()
+    };
+    if db.query(&sans_serif).is_some() {
+        return Some("sans-serif".to_string());
+    }
+
+    // Fontconfig's aliases aren't known here, so guess a sans serif family by its name. Any font
+    // beats no text at all
+    let family = db
+        .faces()
+        .filter_map(|face| face.families.first())
+        .map(|(family, _)| family)
+        .min_by_key(|family| !family.contains("Sans") || family.contains("Mono"));
+    if family.is_none() {
+        warn!("No fonts found, the ring is drawn without text");
+    }
+    family.cloned()
+}
+
+/// Raylib's usual letter spacing for a font size, kept to match `aeonium-gui`
+fn spacing(font_size: f32) -> f32 {
+    font_size / 10.0
+}
+
+fn fill(color: Rgba) -> String {
+    format!(
+        r#"fill="rgb({},{},{})" fill-opacity="{}""#,
+        color.r,
+        color.g,
+        color.b,
+        color.a as f32 / 255.0
+    )
+}
+
+/// SVG path outlining `segment`
+fn ring_segment(geometry: &RingGeometry, segment: Segment) -> String {
+    let point = |radius: f32, angle: f32| {
+        let point = geometry.point(radius, angle);
+        format!("{} {}", point.x, point.y)
+    };
+    // Two arcs per edge, so none of them is ever larger than half a circle
+    let mid_angle = segment.mid_angle();
+    let (outer, inner) = (segment.outer_radius, segment.inner_radius);
+
+    format!(
+        "M {} A {outer} {outer} 0 0 1 {} A {outer} {outer} 0 0 1 {} L {} A {inner} {inner} 0 0 0 {} A {inner} {inner} 0 0 0 {} Z",
+        point(outer, segment.start_angle),
+        point(outer, mid_angle),
+        point(outer, segment.end_angle),
+        point(inner, segment.end_angle),
+        point(inner, mid_angle),
+        point(inner, segment.start_angle),
+    )
+}
+
+/// Escapes text for use in SVG content and attributes
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(count: usize) -> Vec<Item> {
+        (0..count)
+            .map(|idx| Item {
+                name: format!("Entry {idx}"),
+                icon: None,
+                color: None,
+                badges: Vec::new(),
+            })
+            .collect()
+    }
+
+    fn pixel(pixmap: &Pixmap, x: u32, y: u32) -> Rgba {
+        let color = pixmap.pixel(x, y).unwrap().demultiply();
+        Rgba::new(color.red(), color.green(), color.blue(), color.alpha())
+    }
+
+    fn assert_close(actual: Rgba, expected: Rgba) {
+        let channels = |c: Rgba| [c.r, c.g, c.b, c.a];
+        let close = channels(actual)
+            .iter()
+            .zip(channels(expected))
+            .all(|(&a, e)| a.abs_diff(e) <= 2);
+        assert!(close, "expected {expected}, got {actual}");
+    }
+
+    #[test]
+    fn segments_are_coloured_by_highlight_and_item() {
+        let theme = Theme {
+            icon_scale: 0.1,
+            ..Theme::default()
+        };
+        let mut items = items(4);
+        items[2].color = Some(Rgba::new(200, 10, 10, 255));
+
+        let pixmap = render(400, 400, &items, Some(0), &theme).unwrap();
+        // The ring spans radii 75 to 100 around (200, 200), each entry covers a quarter. These
+        // are 80 away from the centre, clear of the icons in the middle of the ring
+        let (inside, outside) = (200 + 57, 200 - 57);
+
+        // First entry starts at the top, so its middle is at the top right
+        assert_close(pixel(&pixmap, inside, outside), theme.highlight_color);
+        assert_close(pixel(&pixmap, inside, inside), theme.segment_color);
+        assert_close(pixel(&pixmap, outside, inside), Rgba::new(200, 10, 10, 255));
+        assert_close(pixel(&pixmap, 5, 5), theme.background);
+    }
+
+    #[test]
+    fn out_of_range_highlight_is_rejected() {
+        assert!(render(100, 100, &items(3), Some(3), &Theme::default()).is_err());
+    }
+}
@@ -0,0 +1,87 @@
+/// Adds scrolling up into whole steps of the ring, keeping what didn't make a step yet
+#[derive(Debug, Default)]
+pub struct ScrollSteps {
+    remainder: f64,
+}
+
+impl ScrollSteps {
+    /// Adds scrolling measured in steps, returns the whole steps completed by it
+    pub fn add_scroll(&mut self, steps: f64) -> i32 {
+        // Turning around drops what was left over from the other direction
+        if self.remainder * steps < 0.0 {
+            self.remainder = 0.0;
+        }
+
+        self.remainder += steps;
+        let whole = self.remainder.trunc();
+        self.remainder -= whole;
+        whole as i32
+    }
+
+    /// Adds touchpad or continuous scrolling, `scroll_threshold` is the distance of one step
+    pub fn add_smooth_scroll(&mut self, value: f64, scroll_threshold: f64) -> i32 {
+        // Lifting the fingers ends the scroll with a 0
+        if value == 0.0 {
+            self.reset();
+            0
+        } else {
+            self.add_scroll(value / scroll_threshold)
+        }
+    }
+
+    /// Drops scrolling that didn't add up to a whole step
+    pub fn reset(&mut self) {
+        self.remainder = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wheel_detents_are_whole_steps() {
+        let mut steps = ScrollSteps::default();
+        assert_eq!(steps.add_scroll(1.0), 1);
+        assert_eq!(steps.add_scroll(-3.0), -3);
+    }
+
+    #[test]
+    fn hi_res_wheels_add_up_to_a_step() {
+        let mut steps = ScrollSteps::default();
+        assert_eq!(steps.add_scroll(0.5), 0);
+        assert_eq!(steps.add_scroll(0.25), 0);
+        assert_eq!(steps.add_scroll(0.5), 1);
+        // The quarter left over carries into the next step
+        assert_eq!(steps.add_scroll(0.75), 1);
+    }
+
+    #[test]
+    fn turning_around_drops_the_remainder() {
+        let mut steps = ScrollSteps::default();
+        assert_eq!(steps.add_scroll(0.75), 0);
+        assert_eq!(steps.add_scroll(-0.5), 0);
+        assert_eq!(steps.add_scroll(-0.5), -1);
+    }
+
+    #[test]
+    fn smooth_scrolling_uses_the_threshold() {
+        let mut steps = ScrollSteps::default();
+        assert_eq!(steps.add_smooth_scroll(10.0, 15.0), 0);
+        assert_eq!(steps.add_smooth_scroll(10.0, 15.0), 1);
+        assert_eq!(steps.add_smooth_scroll(-45.0, 15.0), -3);
+        assert_eq!(steps.add_smooth_scroll(60.0, 30.0), 2);
+    }
+
+    #[test]
+    fn lifting_the_fingers_or_resetting_drops_the_remainder() {
+        let mut steps = ScrollSteps::default();
+        assert_eq!(steps.add_smooth_scroll(10.0, 15.0), 0);
+        assert_eq!(steps.add_smooth_scroll(0.0, 15.0), 0);
+        assert_eq!(steps.add_smooth_scroll(10.0, 15.0), 0);
+
+        steps.reset();
+        assert_eq!(steps.add_scroll(0.5), 0);
+        assert_eq!(steps.add_scroll(0.25), 0);
+    }
+}
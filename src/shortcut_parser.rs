@@ -1,3 +1,5 @@
+use std::cmp::Reverse;
+use std::collections::HashSet;
 use std::os::unix::process::CommandExt;
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
@@ -5,154 +7,586 @@ use std::{env, fs};
 
 use anyhow::{Context, bail};
 use directories::ProjectDirs;
-use freedesktop_entry_parser::parse_entry;
+use freedesktop_entry_parser::{AttrSelector, parse_entry};
 use freedesktop_icons::lookup;
 use tracing::{info, warn};
 
-use crate::utils::{clean_exec_field, convert_to_svg, filter_discord_desktop_files, is_svg};
+use crate::config::{Discovery, Shortcuts, Sort};
+use crate::history::History;
+use crate::utils::{ExecFields, convert_to_svg, find_in_path, is_svg, parse_exec_field};
 
 #[derive(Debug)]
 pub struct DesktopFile {
-    name: String,
+    /// Desktop file ID, e.g. `org.kde.dolphin.desktop`
+    pub id: String,
+    /// Translated `Name`
+    pub name: String,
     exec_path: PathBuf,
     exec_args: Vec<String>,
+    terminal: bool,
+    work_dir: Option<PathBuf>,
+    /// Raw `Icon` key, inherited by actions without one
+    icon_name: Option<String>,
     pub icon: Option<PathBuf>,
+    /// Entries from the `[Desktop Action ...]` groups listed in `Actions`
+    pub actions: Vec<DesktopFile>,
 }
 
-pub fn get_shortcuts(proj_dirs: &ProjectDirs) -> anyhow::Result<Vec<DesktopFile>> {
-    let config_dir = proj_dirs.config_dir();
-    info!("Found config directory: {}", config_dir.display());
+/// Terminal emulators tried in order when none is configured, with the arguments that
+/// precede the command to run
+const KNOWN_TERMINALS: &[(&str, &[&str])] = &[
+    ("x-terminal-emulator", &["-e"]),
+    ("foot", &[]),
+    ("kitty", &[]),
+    ("alacritty", &["-e"]),
+    ("wezterm", &["start", "--"]),
+    ("ghostty", &["-e"]),
+    ("konsole", &["-e"]),
+    ("gnome-terminal", &["--"]),
+    ("xfce4-terminal", &["-x"]),
+    ("xterm", &["-e"]),
+];
 
-    let mut desktop_files = Vec::new();
+/// Picks the command used to wrap `Terminal=true` entries: the configured one,
+/// then `$TERMINAL`, then the first known terminal emulator found in `$PATH`
+pub fn find_terminal(configured: &[String]) -> Option<Vec<String>> {
+    if !configured.is_empty() {
+        return Some(configured.to_vec());
+    }
 
-    let shortcuts_dir = if config_dir.join("shortcuts").is_dir() {
-        config_dir.join("shortcuts")
-    } else {
-        PathBuf::from(
-            env::var("HOME")
-                .context("HOME not set, cannot fallback to ~/.local/share/applications")?,
-        )
-        .join(".local/share/applications")
+    if let Ok(terminal) = env::var("TERMINAL")
+        && find_in_path(&terminal).is_some()
+    {
+        return Some(vec![terminal, "-e".to_string()]);
+    }
+
+    KNOWN_TERMINALS
+        .iter()
+        .find(|(name, _)| find_in_path(name).is_some())
+        .map(|(name, args)| {
+            std::iter::once(*name)
+                .chain(args.iter().copied())
+                .map(str::to_string)
+                .collect()
+        })
+}
+
+/// Finds `.desktop` files in `dir` keyed by their desktop file ID, the path relative to `dir`
+/// with `/` replaced by `-`. Subdirs are only searched when `recursive` is set
+fn desktop_files_in(dir: &Path, recursive: bool) -> std::io::Result<Vec<(String, PathBuf)>> {
+    let mut found = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    // Symlinked dirs can form loops, so every dir is read once at most
+    let mut visited = HashSet::new();
+
+    while let Some(current) = pending.pop() {
+        // Only `dir` itself failing is an error, a broken subdir is skipped on its own
+        let canonical = match fs::canonicalize(&current) {
+            Ok(canonical) => canonical,
+            Err(e) if current == dir => return Err(e),
+            Err(e) => {
+                warn!("Skipping {}: {e}", current.display());
+                continue;
+            }
+        };
+        if !visited.insert(canonical) {
+            continue;
+        }
+
+        let entries = match fs::read_dir(&current) {
+            Ok(entries) => entries,
+            Err(e) if current == dir => return Err(e),
+            Err(e) => {
+                warn!("Skipping {}: {e}", current.display());
+                continue;
+            }
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+
+            if path.is_dir() {
+                if recursive {
+                    pending.push(path);
+                }
+                continue;
+            }
+
+            if path.extension().and_then(|ext| ext.to_str()) != Some("desktop") {
+                continue;
+            }
+
+            let Ok(relative) = path.strip_prefix(dir) else {
+                continue;
+            };
+            let id = relative.to_string_lossy().replace('/', "-");
+            found.push((id, path));
+        }
+    }
+
+    Ok(found)
+}
+
+/// `applications/` dirs from `$XDG_DATA_HOME` and `$XDG_DATA_DIRS`, most important first
+fn xdg_application_dirs() -> anyhow::Result<Vec<PathBuf>> {
+    let data_home = match env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var("HOME").context("HOME not set, cannot find XDG_DATA_HOME")?)
+            .join(".local/share"),
     };
 
-    let entries = std::fs::read_dir(&shortcuts_dir).with_context(|| {
+    let data_dirs = env::var_os("XDG_DATA_DIRS")
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share/:/usr/share/".into());
+
+    Ok(std::iter::once(data_home)
+        .chain(env::split_paths(&data_dirs))
+        .map(|dir| dir.join("applications"))
+        .collect())
+}
+
+/// Desktop files from every XDG data dir, an ID found in an earlier dir shadows later ones
+fn discover_xdg() -> anyhow::Result<Vec<(String, PathBuf)>> {
+    let mut seen = HashSet::new();
+    let mut desktop_paths = Vec::new();
+
+    for dir in xdg_application_dirs()? {
+        let Ok(found) = desktop_files_in(&dir, true) else {
+            continue;
+        };
+        info!("Found {} desktop files in {}", found.len(), dir.display());
+
+        for (id, path) in found {
+            if seen.insert(id.clone()) {
+                desktop_paths.push((id, path));
+            }
+        }
+    }
+
+    Ok(desktop_paths)
+}
+
+/// `shortcuts/` in the config dir, or `~/.local/share/applications` when it doesn't exist
+fn shortcuts_dir(proj_dirs: &ProjectDirs) -> anyhow::Result<PathBuf> {
+    let config_dir = proj_dirs.config_dir();
+
+    if config_dir.join("shortcuts").is_dir() {
+        return Ok(config_dir.join("shortcuts"));
+    }
+
+    Ok(PathBuf::from(
+        env::var("HOME").context("HOME not set, cannot fallback to ~/.local/share/applications")?,
+    )
+    .join(".local/share/applications"))
+}
+
+fn discover_directory(proj_dirs: &ProjectDirs) -> anyhow::Result<Vec<(String, PathBuf)>> {
+    info!("Found config directory: {}", proj_dirs.config_dir().display());
+    let shortcuts_dir = shortcuts_dir(proj_dirs)?;
+
+    desktop_files_in(&shortcuts_dir, false).with_context(|| {
         format!(
             "Failed to read shortcuts directory: {}",
             shortcuts_dir.display()
         )
-    })?;
+    })
+}
+
+/// Dirs searched for desktop files with the given discovery mode, subdirs not included
+pub fn shortcut_dirs(
+    proj_dirs: &ProjectDirs,
+    shortcuts: &Shortcuts,
+) -> anyhow::Result<Vec<PathBuf>> {
+    match shortcuts.discovery {
+        Discovery::Directory => Ok(vec![shortcuts_dir(proj_dirs)?]),
+        Discovery::Xdg => xdg_application_dirs(),
+    }
+}
+
+/// Desktop file IDs and paths found by the configured discovery, limited to `allow`
+pub fn discover_shortcuts(
+    proj_dirs: &ProjectDirs,
+    shortcuts: &Shortcuts,
+) -> anyhow::Result<Vec<(String, PathBuf)>> {
+    let mut desktop_paths = match shortcuts.discovery {
+        Discovery::Directory => discover_directory(proj_dirs)?,
+        Discovery::Xdg => discover_xdg()?,
+    };
+
+    if !shortcuts.allow.is_empty() {
+        desktop_paths.retain(|(id, _)| shortcuts.allow.contains(id));
+
+        for id in &shortcuts.allow {
+            if !desktop_paths.iter().any(|(found, _)| found == id) {
+                warn!("Allowed desktop file `{id}` not found");
+            }
+        }
+    }
 
-    let desktop_paths: Vec<PathBuf> = entries
-        .flatten()
-        .filter(|entry| entry.path().extension().and_then(|ext| ext.to_str()) == Some("desktop"))
-        .map(|entry| entry.path())
-        .filter(|path| filter_discord_desktop_files(path))
-        .collect();
+    Ok(desktop_paths)
+}
 
+pub fn get_shortcuts(
+    proj_dirs: &ProjectDirs,
+    shortcuts: &Shortcuts,
+    history: &History,
+) -> anyhow::Result<Vec<DesktopFile>> {
+    let desktop_paths = discover_shortcuts(proj_dirs, shortcuts)?;
     if desktop_paths.is_empty() {
-        bail!(
-            "No desktop files found in directory: {}",
-            shortcuts_dir.display()
-        );
+        bail!("No desktop files found");
     }
 
-    for path in desktop_paths {
+    let mut desktop_files = Vec::new();
+
+    for (id, path) in desktop_paths {
         match DesktopFile::new(&path, proj_dirs) {
-            Ok(desktop_file) => desktop_files.push(desktop_file),
+            Ok(Some(mut desktop_file)) => {
+                desktop_file.id = id;
+                desktop_files.push(desktop_file);
+            }
+            Ok(None) => {}
             Err(e) => {
                 warn!("Error parsing {}: {e}", path.display());
             }
         }
     }
 
+    if desktop_files.is_empty() {
+        bail!("No usable desktop files found");
+    }
+
+    sort_shortcuts(&mut desktop_files, shortcuts, history);
+
     Ok(desktop_files)
 }
 
+/// Puts the IDs listed in `order` first, everything else follows by frecency when enabled,
+/// then by name so the ring layout doesn't depend on directory iteration order
+pub fn sort_shortcuts(desktop_files: &mut [DesktopFile], shortcuts: &Shortcuts, history: &History) {
+    let order = &shortcuts.order;
+    for id in order {
+        if !desktop_files.iter().any(|desktop_file| &desktop_file.id == id) {
+            warn!("Ordered desktop file `{id}` not found");
+        }
+    }
+
+    let position = |desktop_file: &DesktopFile| {
+        order
+            .iter()
+            .position(|id| id == &desktop_file.id)
+            .unwrap_or(order.len())
+    };
+
+    let score = |desktop_file: &DesktopFile| match shortcuts.sort {
+        Sort::Name => 0.0,
+        Sort::Frecency => history.frecency(&desktop_file.id),
+    };
+
+    // Scores are never negative, so their bit patterns sort like the numbers
+    desktop_files.sort_by_cached_key(|desktop_file| {
+        (
+            position(desktop_file),
+            Reverse(score(desktop_file).to_bits()),
+            desktop_file.name.to_lowercase(),
+            desktop_file.id.clone(),
+        )
+    });
+}
+
+/// Locale names to try for localised keys, most specific first, e.g. `de_DE@euro`, `de_DE`, `de@euro`, `de`
+fn locale_variants() -> Vec<String> {
+    let Some(locale) = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|val| !val.is_empty())
+    else {
+        return Vec::new();
+    };
+
+    let (locale, modifier) = match locale.split_once('@') {
+        Some((locale, modifier)) => (locale, Some(modifier)),
+        None => (locale.as_str(), None),
+    };
+    let locale = locale.split('.').next().unwrap_or_default();
+    let lang = locale.split('_').next().unwrap_or_default();
+
+    let mut variants = Vec::new();
+    if let Some(modifier) = modifier {
+        variants.push(format!("{locale}@{modifier}"));
+    }
+    variants.push(locale.to_string());
+    if let Some(modifier) = modifier {
+        variants.push(format!("{lang}@{modifier}"));
+    }
+    variants.push(lang.to_string());
+    variants.dedup();
+    variants
+}
+
+/// Looks up the icon named by an `Icon` key in the icon theme
+pub fn find_icon(field: &str) -> Option<PathBuf> {
+    lookup(field).with_size(512).with_cache().find()
+}
+
+/// Looks up the icon named by an `Icon` key, SVG icons are rasterised into the cache dir
+fn resolve_icon(field: &str, proj_dirs: &ProjectDirs) -> anyhow::Result<Option<PathBuf>> {
+    let Some(icon_path) = find_icon(field) else {
+        warn!("Icon doesn't exist: {}", field);
+        return Ok(None);
+    };
+
+    let stem = Path::new(&icon_path)
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy();
+
+    let icon_data =
+        fs::read(&icon_path).with_context(|| format!("Failed to read from `{:?}`", icon_path))?;
+
+    if !is_svg(icon_data) {
+        return Ok(Some(icon_path));
+    }
+
+    info!("Icon {} is a SVG file, rasterising...", icon_path.display());
+    let cache_dir = proj_dirs.cache_dir();
+    if !cache_dir.is_dir() {
+        info!(
+            "Cache dir {} doesn't exist, creating...",
+            cache_dir.display()
+        );
+        fs::create_dir_all(cache_dir)?;
+    }
+    let png_path = cache_dir.join(format!("{}.png", stem));
+    if !png_path.is_dir() {
+        convert_to_svg(&icon_path, &png_path)?;
+        info!("Wrote cached icon to {}", png_path.display());
+    } else {
+        info!("Cached icon {} already exists", png_path.display());
+    }
+
+    Ok(Some(png_path))
+}
+
+/// Looks up `key` translated to the current locale, falling back to the untranslated value
+fn localized_attr<'s, T: AsRef<str>>(
+    section: &'s AttrSelector<'_, T>,
+    key: &str,
+) -> Option<&'s str> {
+    locale_variants()
+        .iter()
+        .filter(|locale| !locale.is_empty() && locale.as_str() != "C" && locale.as_str() != "POSIX")
+        .find_map(|locale| section.attr_with_param(key, locale))
+        .or_else(|| section.attr(key))
+}
+
+/// Checks the `Hidden`, `NoDisplay` and `TryExec` keys
+fn skip_reason<T: AsRef<str>>(section: &AttrSelector<'_, T>) -> Option<String> {
+    if section.attr("Hidden") == Some("true") {
+        return Some("`Hidden` is set".to_string());
+    }
+
+    if section.attr("NoDisplay") == Some("true") {
+        return Some("`NoDisplay` is set".to_string());
+    }
+
+    match section.attr("TryExec") {
+        Some(try_exec) if find_in_path(try_exec).is_none() => {
+            Some(format!("`TryExec` binary `{try_exec}` not found"))
+        }
+        _ => None,
+    }
+}
+
 impl DesktopFile {
-    fn new(file_path: impl AsRef<Path>, proj_dirs: &ProjectDirs) -> anyhow::Result<Self> {
+    /// Entry running `true`, for tests elsewhere in the crate
+    #[cfg(test)]
+    pub fn stub(id: &str, name: &str) -> Self {
+        DesktopFile {
+            id: id.to_string(),
+            name: name.to_string(),
+            exec_path: PathBuf::from("true"),
+            exec_args: Vec::new(),
+            terminal: false,
+            work_dir: None,
+            icon_name: None,
+            icon: None,
+            actions: Vec::new(),
+        }
+    }
+
+    /// Parses a desktop file and looks up its icons, returns `None` for entries that shouldn't
+    /// be shown
+    pub fn new(
+        file_path: impl AsRef<Path>,
+        proj_dirs: &ProjectDirs,
+    ) -> anyhow::Result<Option<Self>> {
+        let Some(mut desktop_file) = Self::parse(file_path)? else {
+            return Ok(None);
+        };
+
+        desktop_file.icon = match &desktop_file.icon_name {
+            Some(field) => resolve_icon(field, proj_dirs)?,
+            None => None,
+        };
+
+        for action in &mut desktop_file.actions {
+            action.icon = match &action.icon_name {
+                Some(field) => resolve_icon(field, proj_dirs).unwrap_or_else(|e| {
+                    warn!("Error loading icon of action `{}`: {e}", action.name);
+                    None
+                }),
+                None => None,
+            };
+            if action.icon.is_none() {
+                action.icon = desktop_file.icon.clone();
+            }
+        }
+
+        Ok(Some(desktop_file))
+    }
+
+    /// Parses a desktop file without touching its icons, `icon` stays unset. Returns `None`
+    /// for entries that shouldn't be shown
+    pub fn parse(file_path: impl AsRef<Path>) -> anyhow::Result<Option<Self>> {
         let file_path = file_path.as_ref();
         let entry = parse_entry(file_path)
             .with_context(|| format!("Failed to parse {}", file_path.display()))?;
 
         let desktop_section = entry.section("Desktop Entry");
 
-        let name = desktop_section
-            .attr("Name")
-            .with_context(|| format!("No `Name` section found in {}", file_path.display()))?;
-
-        let exec_attr = desktop_section
-            .attr("Exec")
-            .with_context(|| format!("No `Exec` section found in {}", file_path.display()))?;
-
-        let clean_exec_attr = clean_exec_field(exec_attr);
-
-        let total_exec_cmd: Vec<&str> = clean_exec_attr.split_whitespace().collect();
-        let (exec_path, exec_args) = total_exec_cmd
-            .split_first()
-            .with_context(|| format!("`Exec` field in {} is empty", file_path.display()))?;
+        if let Some(reason) = skip_reason(&desktop_section) {
+            info!("Skipping {}: {reason}", file_path.display());
+            return Ok(None);
+        }
 
-        let mut icon = desktop_section.attr("Icon").and_then(|field| {
-            lookup(field)
-                .with_size(512)
-                .with_cache()
-                .find()
-                .or_else(|| {
-                    warn!("Icon doesn't exist: {}", field);
-                    None
-                })
-        });
+        let mut desktop_file = Self::from_section(&desktop_section, file_path)?;
 
-        if icon.is_none() {
+        if desktop_file.icon_name.is_none() {
             warn!(
                 "No `Icon` field in {}, falling back to default",
                 file_path.display()
             );
-        } else {
-            let icon_path = icon.as_ref().with_context(|| "Icon path was None")?;
-            let stem = Path::new(icon_path)
-                .file_stem()
-                .unwrap_or_default()
-                .to_string_lossy();
-
-            let icon_data = fs::read(icon_path)
-                .with_context(|| format!("Failed to read from `{:?}`", icon_path))?;
-
-            if is_svg(icon_data) {
-                info!("Icon {} is a SVG file, rasterising...", icon_path.display());
-                let cache_dir = proj_dirs.cache_dir();
-                if !cache_dir.is_dir() {
-                    info!(
-                        "Cache dir {} doesn't exist, creating...",
-                        cache_dir.display()
-                    );
-                    fs::create_dir_all(cache_dir)?;
-                }
-                let png_path = cache_dir.join(format!("{}.png", stem));
-                if !png_path.is_dir() {
-                    convert_to_svg(icon_path, &png_path)?;
-                    info!("Wrote cached icon to {}", png_path.display());
-                } else {
-                    info!("Cached icon {} already exists", png_path.display());
+        }
+
+        desktop_file.terminal = desktop_section.attr("Terminal") == Some("true");
+        desktop_file.work_dir = desktop_section
+            .attr("Path")
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from);
+
+        let action_ids = desktop_section
+            .attr("Actions")
+            .unwrap_or_default()
+            .split(';')
+            .filter(|id| !id.is_empty());
+
+        for action_id in action_ids {
+            let action_section = entry.section(format!("Desktop Action {action_id}"));
+
+            match Self::from_section(&action_section, file_path) {
+                Ok(mut action) => {
+                    action.terminal = desktop_file.terminal;
+                    action.work_dir = desktop_file.work_dir.clone();
+                    desktop_file.actions.push(action);
                 }
-                icon = Some(png_path);
+                Err(e) => warn!("Error parsing action `{action_id}`: {e}"),
             }
         }
 
+        Ok(Some(desktop_file))
+    }
+
+    /// Reads the `Name`, `Exec` and `Icon` keys shared by the main entry and its actions
+    fn from_section<T: AsRef<str>>(
+        section: &AttrSelector<'_, T>,
+        file_path: &Path,
+    ) -> anyhow::Result<Self> {
+        let name = localized_attr(section, "Name").with_context(|| {
+            format!(
+                "No `Name` field in [{}] of {}",
+                section.name(),
+                file_path.display()
+            )
+        })?;
+
+        let exec_attr = section.attr("Exec").with_context(|| {
+            format!(
+                "No `Exec` field in [{}] of {}",
+                section.name(),
+                file_path.display()
+            )
+        })?;
+
+        let exec_fields = ExecFields {
+            name,
+            icon: section.attr("Icon"),
+            desktop_file: file_path,
+        };
+
+        let total_exec_cmd = parse_exec_field(exec_attr, &exec_fields)
+            .with_context(|| format!("Invalid `Exec` field in {}", file_path.display()))?;
+        let (exec_path, exec_args) = total_exec_cmd
+            .split_first()
+            .with_context(|| format!("`Exec` field in {} is empty", file_path.display()))?;
+
         Ok(Self {
+            id: String::new(),
             name: name.to_string(),
             exec_path: PathBuf::from(exec_path),
-            exec_args: exec_args.iter().map(|&s| s.to_string()).collect(),
-            icon,
+            exec_args: exec_args.to_vec(),
+            terminal: false,
+            work_dir: None,
+            icon_name: section.attr("Icon").map(str::to_string),
+            icon: None,
+            actions: Vec::new(),
         })
     }
 
-    pub fn spawn_process(&self) -> anyhow::Result<()> {
+    /// `Icon` key as written in the desktop file
+    pub fn icon_name(&self) -> Option<&str> {
+        self.icon_name.as_deref()
+    }
+
+    /// Program started by the `Exec` key
+    pub fn exec_path(&self) -> &Path {
+        &self.exec_path
+    }
+
+    /// Spawns the entry detached from aeonium, `Terminal=true` entries are wrapped in `terminal`
+    pub fn spawn_process(&self, terminal: Option<&[String]>) -> anyhow::Result<()> {
         info!(
             "Attempting to spawn {} with args {:?}",
             self.exec_path.display(),
             self.exec_args
         );
-        let mut child_proc = Command::new(&self.exec_path);
+
+        let mut child_proc = if self.terminal {
+            let Some((term_path, term_args)) = terminal.and_then(|cmd| cmd.split_first()) else {
+                bail!(
+                    "{} needs a terminal but no terminal emulator was found",
+                    self.name
+                );
+            };
+
+            info!("Running {} inside {}", self.name, term_path);
+            let mut cmd = Command::new(term_path);
+            cmd.args(term_args).arg(&self.exec_path);
+            cmd
+        } else {
+            Command::new(&self.exec_path)
+        };
+
+        match &self.work_dir {
+            Some(work_dir) if work_dir.is_dir() => {
+                child_proc.current_dir(work_dir);
+            }
+            Some(work_dir) => warn!(
+                "Path {} of {} isn't a directory, starting it in the current one",
+                work_dir.display(),
+                self.name
+            ),
+            None => {}
+        }
 
         child_proc
             .args(&self.exec_args)
@@ -178,3 +612,77 @@ impl DesktopFile {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::symlink;
+
+    use super::*;
+
+    fn ids(desktop_files: &[DesktopFile]) -> Vec<&str> {
+        desktop_files
+            .iter()
+            .map(|desktop_file| desktop_file.id.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn ordered_entries_come_first_and_the_rest_by_name() {
+        let mut desktop_files = vec![
+            DesktopFile::stub("zed.desktop", "Zed"),
+            DesktopFile::stub("mail.desktop", "mail"),
+            DesktopFile::stub("firefox.desktop", "Firefox"),
+            DesktopFile::stub("alacritty.desktop", "Alacritty"),
+            DesktopFile::stub("term.desktop", "Alacritty"),
+        ];
+        let shortcuts = Shortcuts {
+            order: vec![
+                "zed.desktop".to_string(),
+                "missing.desktop".to_string(),
+                "firefox.desktop".to_string(),
+            ],
+            ..Shortcuts::default()
+        };
+        let history = History::empty(PathBuf::from("/nonexistent/history"));
+
+        sort_shortcuts(&mut desktop_files, &shortcuts, &history);
+
+        // Names compare without case, the ID breaks ties
+        assert_eq!(
+            ids(&desktop_files),
+            [
+                "zed.desktop",
+                "firefox.desktop",
+                "alacritty.desktop",
+                "term.desktop",
+                "mail.desktop",
+            ]
+        );
+    }
+
+    #[test]
+    fn subdirs_are_searched_once_when_recursive() {
+        let dir = env::temp_dir().join(format!("aeonium-desktop-files-{}", std::process::id()));
+        fs::create_dir_all(dir.join("kde")).unwrap();
+        fs::write(dir.join("files.desktop"), "").unwrap();
+        fs::write(dir.join("kde/dolphin.desktop"), "").unwrap();
+        fs::write(dir.join("notes.txt"), "").unwrap();
+        // Would recurse forever if followed blindly
+        symlink(&dir, dir.join("kde/loop")).unwrap();
+
+        let ids = |recursive| {
+            let mut ids: Vec<String> = desktop_files_in(&dir, recursive)
+                .unwrap()
+                .into_iter()
+                .map(|(id, _)| id)
+                .collect();
+            ids.sort();
+            ids
+        };
+        let (flat, recursive) = (ids(false), ids(true));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(flat, ["files.desktop"]);
+        assert_eq!(recursive, ["files.desktop", "kde-dolphin.desktop"]);
+    }
+}
@@ -0,0 +1,232 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::{Context, bail, ensure};
+use serde::{Deserialize, Serialize};
+
+/// A colour written as `#RRGGBB` or `#RRGGBBAA`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Rgba {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Rgba {
+    pub const fn new(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Rgba { r, g, b, a }
+    }
+}
+
+impl FromStr for Rgba {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s
+            .strip_prefix('#')
+            .with_context(|| format!("Colour `{s}` must start with `#`"))?;
+
+        if hex.len() != 6 && hex.len() != 8 {
+            bail!("Colour `{s}` must be written as #RRGGBB or #RRGGBBAA");
+        }
+        // `from_str_radix` alone would also take a sign, e.g. `#+F+F+F`
+        if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            bail!("Invalid hex digits in colour `{s}`");
+        }
+
+        let channel =
+            |idx: usize| u8::from_str_radix(&hex[idx..idx + 2], 16).expect("checked hex digits");
+
+        Ok(Rgba {
+            r: channel(0),
+            g: channel(2),
+            b: channel(4),
+            a: if hex.len() == 8 { channel(6) } else { 255 },
+        })
+    }
+}
+
+impl TryFrom<String> for Rgba {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for Rgba {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{:02X}{:02X}{:02X}{:02X}",
+            self.r, self.g, self.b, self.a
+        )
+    }
+}
+
+impl From<Rgba> for String {
+    fn from(color: Rgba) -> Self {
+        color.to_string()
+    }
+}
+
+/// Look of the ring drawn by `aeonium-gui`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    pub segment_color: Rgba,
+    pub highlight_color: Rgba,
+    /// Drawn around the highlighted segment, `outline_width = 0` disables it
+    pub outline_color: Rgba,
+    /// Outline thickness in pixels
+    pub outline_width: f32,
+    /// Fills the screen behind the ring, e.g. `#00000080` to dim the desktop
+    pub background: Rgba,
+    /// Outer radius as a fraction of the shorter screen side
+    pub outer_radius: f32,
+    /// Inner radius as a fraction of the outer radius
+    pub inner_radius: f32,
+    /// Gap between segments in degrees
+    pub gap: f32,
+    /// Icon size as a fraction of the ring thickness
+    pub icon_scale: f32,
+    /// TTF or OTF file used for names, raylib's built in font when unset
+    pub font: Option<PathBuf>,
+    /// Size of the highlighted name in the ring centre
+    pub font_size: f32,
+    pub text_color: Rgba,
+    /// Also write every name next to its segment
+    pub segment_labels: bool,
+    pub label_font_size: f32,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            segment_color: Rgba::new(100, 149, 237, 77),
+            highlight_color: Rgba::new(31, 102, 229, 220),
+            outline_color: Rgba::new(255, 255, 255, 220),
+            outline_width: 0.0,
+            background: Rgba::new(0, 0, 0, 0),
+            outer_radius: 0.25,
+            inner_radius: 0.75,
+            gap: 2.0,
+            icon_scale: 0.7,
+            font: None,
+            font_size: 32.0,
+            text_color: Rgba::new(255, 255, 255, 255),
+            segment_labels: false,
+            label_font_size: 18.0,
+        }
+    }
+}
+
+impl Theme {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        ensure!(
+            self.outer_radius > 0.0 && self.outer_radius <= 0.5,
+            "`theme.outer_radius` must be between 0 and 0.5"
+        );
+        ensure!(
+            (0.0..1.0).contains(&self.inner_radius),
+            "`theme.inner_radius` must be at least 0 and less than 1"
+        );
+        ensure!(
+            (0.0..45.0).contains(&self.gap),
+            "`theme.gap` must be at least 0 and less than 45 degrees"
+        );
+        ensure!(
+            self.icon_scale > 0.0 && self.icon_scale <= 1.0,
+            "`theme.icon_scale` must be between 0 and 1"
+        );
+        ensure!(
+            self.outline_width >= 0.0,
+            "`theme.outline_width` can't be negative"
+        );
+        ensure!(
+            self.font_size > 0.0 && self.label_font_size > 0.0,
+            "`theme.font_size` and `theme.label_font_size` must be positive"
+        );
+        if let Some(font) = &self.font {
+            ensure!(font.is_file(), "Font {} not found", font.display());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colours_parse_with_and_without_alpha() {
+        assert_eq!(
+            "#6495ED".parse::<Rgba>().unwrap(),
+            Rgba::new(100, 149, 237, 255)
+        );
+        assert_eq!(
+            "#1f66e5dc".parse::<Rgba>().unwrap(),
+            Rgba::new(31, 102, 229, 220)
+        );
+    }
+
+    #[test]
+    fn colours_round_trip_through_display() {
+        for color in [
+            Rgba::new(0, 0, 0, 0),
+            Rgba::new(100, 149, 237, 77),
+            Rgba::new(255, 255, 255, 255),
+        ] {
+            assert_eq!(color.to_string().parse::<Rgba>().unwrap(), color);
+        }
+        assert_eq!(
+            "#1f66e5dc".parse::<Rgba>().unwrap().to_string(),
+            "#1F66E5DC"
+        );
+    }
+
+    #[test]
+    fn invalid_colours_are_rejected() {
+        for s in [
+            "6495ED",
+            "#6495E",
+            "#6495ED4",
+            "#6495EDFF00",
+            "#GG95ED",
+            "#+F+F+F",
+            "#-1-1-1",
+            "#ÄÄÄ",
+            "",
+        ] {
+            assert!(s.parse::<Rgba>().is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn default_theme_is_valid() {
+        Theme::default().validate().unwrap();
+    }
+
+    #[test]
+    fn out_of_range_values_are_rejected() {
+        let invalid: [fn(&mut Theme); 8] = [
+            |theme| theme.outer_radius = 0.0,
+            |theme| theme.outer_radius = 0.6,
+            |theme| theme.inner_radius = 1.0,
+            |theme| theme.gap = 45.0,
+            |theme| theme.icon_scale = 0.0,
+            |theme| theme.outline_width = -1.0,
+            |theme| theme.label_font_size = 0.0,
+            |theme| theme.font = Some(PathBuf::from("/nonexistent/font.ttf")),
+        ];
+
+        for (idx, change) in invalid.into_iter().enumerate() {
+            let mut theme = Theme::default();
+            change(&mut theme);
+            assert!(theme.validate().is_err(), "change {idx}");
+        }
+    }
+}
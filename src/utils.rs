@@ -1,7 +1,8 @@
-use std::fs;
-use std::path::Path;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::{env, fs};
 
-use anyhow::Context;
+use anyhow::{Context, bail};
 use resvg::tiny_skia::Pixmap;
 use resvg::usvg::{self, Transform};
 
@@ -39,28 +40,313 @@ where
     Ok(())
 }
 
-#[inline]
-pub fn filter_discord_desktop_files(desktop_files: &Path) -> bool {
-    let Some(stem) = desktop_files.file_stem().and_then(|s| s.to_str()) else {
-        return true;
-    };
-    if let Some(rest) = stem.strip_prefix("discord-") {
-        !rest.chars().all(|c| c.is_ascii_digit())
-    } else {
-        true
+/// Replaces the contents of `path` through a temporary file, so readers never see it half written
+pub fn write_atomic(path: &Path, contents: &str) -> anyhow::Result<()> {
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    fs::write(&tmp_path, contents)
+        .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+    fs::rename(&tmp_path, path).with_context(|| format!("Failed to replace {}", path.display()))?;
+    Ok(())
+}
+
+fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+/// Resolves `program` to an executable file like a shell would, paths containing a `/` aren't
+/// looked up in `$PATH`
+pub fn find_in_path(program: &str) -> Option<PathBuf> {
+    if program.contains('/') {
+        let path = PathBuf::from(program);
+        return is_executable(&path).then_some(path);
     }
+
+    let paths = env::var_os("PATH")?;
+    env::split_paths(&paths)
+        .map(|dir| dir.join(program))
+        .find(|candidate| is_executable(candidate))
 }
 
-// TODO: Comply with free desktop specification instead of ignoring it
-pub fn clean_exec_field(exec_field: &str) -> String {
-    let mut out = String::new();
-    let mut exec_field_iter = exec_field.chars();
-    while let Some(c) = exec_field_iter.next() {
-        if c == '%' {
-            exec_field_iter.next();
+/// Values substituted for the field codes of an `Exec` key
+pub struct ExecFields<'a> {
+    /// Translated `Name` of the entry, used for `%c`
+    pub name: &'a str,
+    /// Raw `Icon` key, used for `%i`
+    pub icon: Option<&'a str>,
+    /// Location of the desktop file, used for `%k`
+    pub desktop_file: &'a Path,
+}
+
+enum ExecPiece {
+    Literal(String),
+    Field(char),
+}
+
+/// Splits an `Exec` key into arguments following the Desktop Entry specification.
+///
+/// The general string escapes (`\s`, `\n`, `\t`, `\r`, `\\`) are applied first,
+/// then the quoting rules and finally field code expansion. No files or URLs are ever
+/// passed, so `%f`, `%F`, `%u` and `%U` expand to nothing. Field codes inside quotes are
+/// forbidden by the specification and rejected.
+pub fn parse_exec_field(exec_field: &str, fields: &ExecFields) -> anyhow::Result<Vec<String>> {
+    let unescaped = unescape_string(exec_field);
+    let mut args = Vec::new();
+
+    for pieces in split_exec_args(&unescaped)? {
+        if let [ExecPiece::Field(code)] = pieces.as_slice() {
+            args.extend(expand_standalone_field(*code, fields)?);
             continue;
         }
-        out.push(c);
+
+        let mut arg = String::new();
+        for piece in pieces {
+            match piece {
+                ExecPiece::Literal(text) => arg.push_str(&text),
+                ExecPiece::Field(code) => arg.push_str(&expand_inline_field(code, fields)?),
+            }
+        }
+        args.push(arg);
+    }
+
+    Ok(args)
+}
+
+/// Applies the escape sequences of the `string` value type, unknown sequences are kept
+fn unescape_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('s') => out.push(' '),
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some('\\') => out.push('\\'),
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
     }
     out
 }
+
+fn split_exec_args(exec: &str) -> anyhow::Result<Vec<Vec<ExecPiece>>> {
+    let mut args = Vec::new();
+    let mut pieces: Vec<ExecPiece> = Vec::new();
+    let mut literal = String::new();
+    let mut in_arg = false;
+    let mut in_quotes = false;
+    let mut chars = exec.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                in_arg = true;
+            }
+            '\\' if in_quotes => match chars.next() {
+                Some(escaped @ ('"' | '`' | '$' | '\\')) => literal.push(escaped),
+                Some(other) => {
+                    literal.push('\\');
+                    literal.push(other);
+                }
+                None => bail!("Unterminated quote in `{exec}`"),
+            },
+            '\\' => {
+                // Reserved characters outside of quotes are invalid, accept the common
+                // shell style escape anyway
+                literal.push(chars.next().unwrap_or('\\'));
+                in_arg = true;
+            }
+            '%' => {
+                let code = chars
+                    .next()
+                    .with_context(|| format!("Dangling `%` at the end of `{exec}`"))?;
+
+                if code == '%' {
+                    literal.push('%');
+                } else if in_quotes {
+                    bail!("Field code `%{code}` inside quotes in `{exec}`");
+                } else {
+                    if !literal.is_empty() {
+                        pieces.push(ExecPiece::Literal(std::mem::take(&mut literal)));
+                    }
+                    pieces.push(ExecPiece::Field(code));
+                }
+                in_arg = true;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if in_arg {
+                    if !literal.is_empty() {
+                        pieces.push(ExecPiece::Literal(std::mem::take(&mut literal)));
+                    }
+                    args.push(std::mem::take(&mut pieces));
+                    in_arg = false;
+                }
+            }
+            c => {
+                literal.push(c);
+                in_arg = true;
+            }
+        }
+    }
+
+    if in_quotes {
+        bail!("Unterminated quote in `{exec}`");
+    }
+
+    if in_arg {
+        if !literal.is_empty() {
+            pieces.push(ExecPiece::Literal(literal));
+        }
+        args.push(pieces);
+    }
+
+    Ok(args)
+}
+
+/// Expands a field code that makes up a whole argument, which may produce any number of arguments
+fn expand_standalone_field(code: char, fields: &ExecFields) -> anyhow::Result<Vec<String>> {
+    Ok(match code {
+        'i' => match fields.icon {
+            Some(icon) if !icon.is_empty() => vec!["--icon".to_string(), icon.to_string()],
+            _ => Vec::new(),
+        },
+        'f' | 'F' | 'u' | 'U' | 'd' | 'D' | 'n' | 'N' | 'v' | 'm' => Vec::new(),
+        code => vec![expand_inline_field(code, fields)?],
+    })
+}
+
+/// Expands a field code that is part of a larger argument
+fn expand_inline_field(code: char, fields: &ExecFields) -> anyhow::Result<String> {
+    Ok(match code {
+        'c' => fields.name.to_string(),
+        'k' => fields.desktop_file.to_string_lossy().into_owned(),
+        'i' => fields.icon.unwrap_or_default().to_string(),
+        'f' | 'F' | 'u' | 'U' | 'd' | 'D' | 'n' | 'N' | 'v' | 'm' => String::new(),
+        code => bail!("Unknown field code `%{code}`"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(exec: &str) -> anyhow::Result<Vec<String>> {
+        parse_exec_field(
+            exec,
+            &ExecFields {
+                name: "Files",
+                icon: Some("system-file-manager"),
+                desktop_file: Path::new("/usr/share/applications/files.desktop"),
+            },
+        )
+    }
+
+    #[test]
+    fn paths_must_be_executable_files() {
+        let dir = env::temp_dir().join(format!("aeonium-exec-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let program = dir.join("program");
+        fs::write(&program, "#!/bin/sh\n").unwrap();
+
+        let found = |path: &Path| find_in_path(path.to_str().unwrap());
+        let not_executable = found(&program);
+        fs::set_permissions(&program, fs::Permissions::from_mode(0o755)).unwrap();
+        let executable = found(&program);
+        let directory = found(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(not_executable, None);
+        assert_eq!(executable, Some(program));
+        assert_eq!(directory, None);
+        assert!(find_in_path("/nonexistent/program").is_none());
+    }
+
+    #[test]
+    fn arguments_are_split_on_whitespace() {
+        assert_eq!(
+            parse("nautilus  --new-window\tfoo").unwrap(),
+            ["nautilus", "--new-window", "foo"]
+        );
+    }
+
+    #[test]
+    fn quoted_arguments_keep_their_spaces() {
+        assert_eq!(
+            parse(r#""/opt/My App/run" --title "a b"c """#).unwrap(),
+            ["/opt/My App/run", "--title", "a bc", ""]
+        );
+    }
+
+    #[test]
+    fn escapes_inside_quotes() {
+        // As written in the desktop file, where each `\\` is an escaped backslash
+        assert_eq!(
+            parse(r#"sh -c "echo \\"\\`\\$HOME\\\\""#).unwrap(),
+            ["sh", "-c", r#"echo "`$HOME\"#]
+        );
+    }
+
+    #[test]
+    fn double_percent_is_a_literal_percent() {
+        assert_eq!(
+            parse(r#"printf 100%% "50%%""#).unwrap(),
+            ["printf", "100%", "50%"]
+        );
+    }
+
+    #[test]
+    fn file_and_url_codes_expand_to_nothing() {
+        assert_eq!(parse("app %f %F %u %U").unwrap(), ["app"]);
+        assert_eq!(parse("app --open=%u").unwrap(), ["app", "--open="]);
+    }
+
+    #[test]
+    fn icon_expands_to_two_arguments() {
+        assert_eq!(
+            parse("app %i").unwrap(),
+            ["app", "--icon", "system-file-manager"]
+        );
+
+        let fields = ExecFields {
+            name: "Files",
+            icon: None,
+            desktop_file: Path::new("files.desktop"),
+        };
+        assert_eq!(parse_exec_field("app %i", &fields).unwrap(), ["app"]);
+    }
+
+    #[test]
+    fn name_and_location_are_substituted() {
+        assert_eq!(
+            parse("app --class=%c %k").unwrap(),
+            [
+                "app",
+                "--class=Files",
+                "/usr/share/applications/files.desktop"
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_exec_keys_are_rejected() {
+        assert!(parse(r#"app "unterminated"#).is_err());
+        assert!(parse(r#"app "%i""#).is_err());
+        assert!(parse(r#"app "--name=%c""#).is_err());
+        assert!(parse("app %z").is_err());
+        assert!(parse("app %").is_err());
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::Context;
+use directories::ProjectDirs;
+use inotify::{EventMask, Inotify, WatchDescriptor, WatchMask};
+use tracing::{debug, warn};
+
+use crate::config::{Config, Discovery};
+use crate::gui_state::EventType;
+use crate::shortcut_parser::shortcut_dirs;
+
+/// Editors and package managers touch several files at once, wait for them to settle
+const SETTLE_TIME: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Watched {
+    ConfigDir,
+    ShortcutDir,
+}
+
+fn watch_mask() -> WatchMask {
+    WatchMask::CLOSE_WRITE
+        | WatchMask::CREATE
+        | WatchMask::DELETE
+        | WatchMask::MOVED_FROM
+        | WatchMask::MOVED_TO
+}
+
+/// `dir` and every dir below it, inotify watches aren't recursive
+fn dir_tree(dir: &Path) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    // Symlinked dirs can form loops
+    let mut visited = HashSet::new();
+
+    while let Some(current) = pending.pop() {
+        let Ok(canonical) = fs::canonicalize(&current) else {
+            continue;
+        };
+        if !visited.insert(canonical) {
+            continue;
+        }
+        let Ok(entries) = fs::read_dir(&current) else {
+            continue;
+        };
+        pending.extend(entries.flatten().map(|e| e.path()).filter(|p| p.is_dir()));
+        dirs.push(current);
+    }
+
+    dirs
+}
+
+/// Whether an event in a watched dir can change the config or the shortcuts
+fn is_relevant(watched: Watched, mask: EventMask, name: Option<&OsStr>) -> bool {
+    if mask.contains(EventMask::Q_OVERFLOW) {
+        return true;
+    }
+
+    let Some(name) = name.and_then(OsStr::to_str) else {
+        return false;
+    };
+
+    match watched {
+        // `shortcuts` appearing switches where `directory` discovery looks
+        Watched::ConfigDir => name == "config.toml" || name == "shortcuts",
+        Watched::ShortcutDir => mask.contains(EventMask::ISDIR) || name.ends_with(".desktop"),
+    }
+}
+
+/// Watches `config.toml` and the shortcut dirs, sending a `Reload` event when they change
+pub fn run_watcher(tx: Sender<EventType>, proj_dirs: ProjectDirs) -> anyhow::Result<()> {
+    let mut inotify = Inotify::init().context("Failed to initialize inotify")?;
+    let mut buffer = [0; 4096];
+    let mut dirs = Vec::new();
+    let mut recursive = false;
+
+    loop {
+        // The dirs to watch depend on the config, so they're looked up again after every change.
+        // Only read here, writing the config is up to the reload in the main thread
+        match Config::read(&proj_dirs).and_then(|config| {
+            let new_dirs = shortcut_dirs(&proj_dirs, &config.shortcuts)?;
+            // Only XDG discovery looks into subdirs
+            Ok((new_dirs, config.shortcuts.discovery == Discovery::Xdg))
+        }) {
+            Ok(new_dirs) => (dirs, recursive) = new_dirs,
+            Err(e) => warn!("Keeping the previous watched dirs: {:?}", e),
+        }
+
+        let mut watches: HashMap<WatchDescriptor, Watched> = HashMap::new();
+        let config_wd = inotify
+            .watches()
+            .add(proj_dirs.config_dir(), watch_mask())
+            .context("Failed to watch the config dir")?;
+        watches.insert(config_wd, Watched::ConfigDir);
+
+        let watched_dirs = dirs.iter().flat_map(|dir| {
+            if recursive {
+                dir_tree(dir)
+            } else {
+                vec![dir.clone()]
+            }
+        });
+        for dir in watched_dirs {
+            match inotify.watches().add(&dir, watch_mask()) {
+                Ok(wd) => {
+                    debug!("Watching {}", dir.display());
+                    watches.entry(wd).or_insert(Watched::ShortcutDir);
+                }
+                Err(e) => debug!("Not watching {}: {e}", dir.display()),
+            }
+        }
+
+        'wait: loop {
+            let events = inotify
+                .read_events_blocking(&mut buffer)
+                .context("Failed to read inotify events")?;
+
+            for event in events {
+                let watched = watches
+                    .get(&event.wd)
+                    .copied()
+                    .unwrap_or(Watched::ShortcutDir);
+                if is_relevant(watched, event.mask, event.name) {
+                    break 'wait;
+                }
+            }
+        }
+
+        thread::sleep(SETTLE_TIME);
+        while inotify
+            .read_events(&mut buffer)
+            .is_ok_and(|mut events| events.next().is_some())
+        {}
+
+        for wd in watches.into_keys() {
+            // Fails for dirs that were deleted in the meantime
+            let _ = inotify.watches().remove(wd);
+        }
+
+        tx.send(EventType::Reload)
+            .context("Failed to send Reload event")?;
+    }
+}